@@ -1,48 +1,165 @@
+mod mod_animation;
+mod mod_args;
+mod mod_ascii;
+mod mod_cells;
+mod mod_config;
+mod mod_detect;
 mod mod_display;
+mod mod_event_loop;
 mod mod_events;
+mod mod_iterm;
+mod mod_keymap;
+mod mod_kitty;
+mod mod_loader;
+mod mod_palette;
+mod mod_playlist;
+mod mod_pyramid;
+mod mod_screen;
+mod mod_sixel;
 
-use std::io::stdout;
+use std::io::{stdout, IsTerminal};
 
 use crossterm::cursor::MoveToColumn;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
 use crossterm::execute;
 use crossterm::style::Print;
 use crossterm::terminal::{
-    disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen,
+    self, disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+    LeaveAlternateScreen,
 };
-use image::open;
-use mod_display::{display, DisplayInfo};
-use mod_events::handle_events;
+use mod_detect::{detect_color_mode, detect_renderer};
+use mod_display::{display, Background, DisplayInfo, Renderer};
+use mod_event_loop::EventLoop;
+use mod_events::{attach_tty, handle_events, schedule_timers, ViewerState};
+use mod_loader::{open_image, ImageState};
+use mod_palette::{ColorMode, Dither};
+use mod_playlist::{Playlist, Slideshow, DEFAULT_INTERVAL};
+use mod_pyramid::{Filter, Pyramid};
+use mod_screen::Screen;
 
 fn main() {
-    let args = std::env::args().collect::<Vec<String>>();
-    let image_path = if args.len() > 1 {
-        &args[1]
-    } else {
-        "sample.png"
+    let args = mod_args::parse(&std::env::args().skip(1).collect::<Vec<String>>());
+    mod_config::init(mod_config::load().unwrap_or_else(|e| {
+        eprintln!("invalid config {}", e);
+        std::process::exit(2);
+    }));
+    let config = mod_config::config();
+    let renderer = args.renderer.unwrap_or(config.renderer);
+    let mut playlist = Playlist::new(&args.paths).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    if args.shuffle {
+        playlist.shuffle();
+    }
+    let color_mode = match args.color_mode.unwrap_or(config.color_mode) {
+        Some(color_mode) => color_mode,
+        // keep captured logs free of escape sequences,
+        // unless a renderer was asked for, which needs its colours
+        None if args.print && renderer.is_none() && !stdout().is_terminal() => ColorMode::Mono,
+        None => detect_color_mode(),
     };
 
-    // set terminal
-    execute!(stdout(), EnterAlternateScreen).unwrap();
-    enable_raw_mode().unwrap();
-
-    // main process
     let mut info = DisplayInfo {
-        image_file_path: image_path.to_string(),
+        image_file_path: playlist.current().to_string(),
         magnify: 1.0,
         center: (-1.0, -1.0),
         clip_size: (-1.0, -1.0),
         img_size: (0, 0),
+        mapping: None,
         show_help: false,
+        inspect: None,
+        watch: args.watch,
+        notice: None,
+        slideshow: Slideshow {
+            interval: args.slideshow.unwrap_or(DEFAULT_INTERVAL),
+            looping: args.looping,
+            active: args.slideshow.is_some(),
+            paused: false,
+        },
+        renderer: renderer.unwrap_or(Renderer::HalfBlock),
+        color_mode,
+        dither: args
+            .dither
+            .or(config.dither)
+            .unwrap_or(Dither::FloydSteinberg),
+        filter: args.filter.or(config.filter).unwrap_or(Filter::Auto),
+        position: playlist.position(),
+        scale: args.scale,
+        start_center: args.center,
+        background: args
+            .background
+            .or(config.background)
+            .unwrap_or(Background::Checker),
     };
-    let mut image = open(&info.image_file_path);
-    display(&image, &mut info);
+
+    // render once and exit
+    if args.print {
+        let cols = args.width.unwrap_or_else(|| {
+            if stdout().is_terminal() {
+                terminal::size().map(|(w, _)| w).unwrap_or(80)
+            } else {
+                80
+            }
+        });
+        // plain text keeps captured logs readable
+        if renderer.is_none() && !stdout().is_terminal() {
+            info.renderer = Renderer::Ascii;
+        }
+        let mut failed = false;
+        for path in playlist.paths() {
+            match open_image(path) {
+                Ok(image) => {
+                    info.center = (-1.0, -1.0);
+                    mod_display::print(&Pyramid::new(image), &mut info, cols, args.height);
+                }
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    failed = true;
+                }
+            }
+        }
+        if failed {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // set terminal
+    if let Err(e) = attach_tty() {
+        eprintln!("cannot open the terminal: {}", e);
+        std::process::exit(1);
+    }
+    if args.alt_screen {
+        execute!(stdout(), EnterAlternateScreen).unwrap();
+    }
+    enable_raw_mode().unwrap();
+    if args.mouse {
+        execute!(stdout(), EnableMouseCapture).unwrap();
+    }
+    info.renderer = renderer.unwrap_or_else(detect_renderer);
+
+    // main process
+    let mut screen = Screen::default();
+    let mut events = EventLoop::default();
+    let mut image = ImageState::load(&info.image_file_path, events.decoder());
+    display(&image, &mut info, &mut screen);
     let mut current_info = info.clone();
+    let mut state = ViewerState::default();
 
     loop {
-        match handle_events(&mut image, &mut info) {
+        schedule_timers(&mut events, &image, &info);
+        match handle_events(
+            &mut events,
+            &mut playlist,
+            &mut image,
+            &mut info,
+            &mut state,
+        ) {
             Ok(true) => {
-                if current_info != info {
-                    display(&image, &mut info);
+                let changed = image.update();
+                if changed || image.is_loading() || current_info != info {
+                    display(&image, &mut info, &mut screen);
                     current_info = info.clone();
                 }
             }
@@ -58,6 +175,15 @@ fn main() {
     }
 
     // reset terminal
-    execute!(stdout(), LeaveAlternateScreen).unwrap();
+    mod_kitty::clear();
+    if args.mouse {
+        execute!(stdout(), DisableMouseCapture).unwrap();
+    }
+    if args.alt_screen {
+        execute!(stdout(), LeaveAlternateScreen).unwrap();
+    } else {
+        // keep the last frame and start the shell prompt below the status line
+        execute!(stdout(), Print("\r\n")).unwrap();
+    }
     disable_raw_mode().unwrap();
 }
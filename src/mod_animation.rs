@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+
+use crate::mod_pyramid::Pyramid;
+
+// delays this short are treated as missing, as browsers do
+const MIN_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+const MIN_SPEED: f64 = 0.125;
+const MAX_SPEED: f64 = 8.0;
+
+pub struct Frame {
+    pub image: Pyramid,
+    /// How long the frame is shown at normal speed.
+    pub delay: Duration,
+}
+
+/// Frames of an image together with the playback state.
+/// Still images are animations with a single frame.
+pub struct Animation {
+    frames: Vec<Frame>,
+    index: usize,
+    paused: bool,
+    speed: f64,
+    /// When the next frame is due while playing.
+    due: Instant,
+    /// Whether the controls changed something since the last `advance`.
+    changed: bool,
+}
+
+impl Animation {
+    /// Starts playing `frames`, which must not be empty.
+    pub fn new(mut frames: Vec<Frame>) -> Animation {
+        for frame in &mut frames {
+            if frame.delay < MIN_DELAY {
+                frame.delay = DEFAULT_DELAY;
+            }
+        }
+        let due = Instant::now() + frames[0].delay;
+        Animation {
+            frames,
+            index: 0,
+            paused: false,
+            speed: 1.0,
+            due,
+            changed: false,
+        }
+    }
+
+    pub fn still(image: Pyramid) -> Animation {
+        Animation::new(vec![Frame {
+            image,
+            delay: Duration::ZERO,
+        }])
+    }
+
+    /// The frame to show.
+    pub fn image(&self) -> &Pyramid {
+        &self.frames[self.index].image
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+
+    /// 1-based index of the shown frame and the number of frames.
+    pub fn position(&self) -> (usize, usize) {
+        (self.index + 1, self.frames.len())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Time until the next frame, `None` for stills and paused animations.
+    pub fn time_to_next_frame(&self) -> Option<Duration> {
+        if !self.is_animated() || self.paused {
+            return None;
+        }
+        Some(self.due.saturating_duration_since(Instant::now()))
+    }
+
+    /// Shows the next frame if it is due. Returns whether the frame or
+    /// the playback state changed since the last call.
+    pub fn advance(&mut self) -> bool {
+        let changed = std::mem::take(&mut self.changed);
+        if self.time_to_next_frame() != Some(Duration::ZERO) {
+            return changed;
+        }
+        self.index = (self.index + 1) % self.frames.len();
+        self.due += self.delay();
+        // start over from now instead of racing through frames when drawing falls behind
+        let now = Instant::now();
+        if self.due < now {
+            self.due = now + self.delay();
+        }
+        true
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.changed = true;
+        if !self.paused {
+            self.due = Instant::now() + self.delay();
+        }
+    }
+
+    /// Pauses and shows the next or the previous frame.
+    pub fn step(&mut self, forward: bool) {
+        let count = self.frames.len();
+        self.paused = true;
+        self.changed = true;
+        self.index = if forward {
+            (self.index + 1) % count
+        } else {
+            (self.index + count - 1) % count
+        };
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed * 2.0).min(MAX_SPEED);
+        self.changed = true;
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = (self.speed / 2.0).max(MIN_SPEED);
+        self.changed = true;
+    }
+
+    // delay of the shown frame at the current speed
+    fn delay(&self) -> Duration {
+        self.frames[self.index].delay.div_f64(self.speed)
+    }
+}
@@ -0,0 +1,350 @@
+use std::io::{stdout, Write};
+use std::time::Duration;
+
+use crate::mod_display::{Background, Renderer, Scale};
+use crate::mod_palette::{ColorMode, Dither};
+use crate::mod_pyramid::Filter;
+
+// shorter slideshow intervals would advance on every wake of the event loop
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+const USAGE: &str = "\
+Usage: img_in_term [OPTIONS] <PATH>...
+       img_in_term cat [OPTIONS] <PATH>...
+
+Shows images in the terminal. PATH may be a file, a directory or a glob pattern,
+or `-` to read an image from standard input.
+`cat` is the same as --print.
+
+Options:
+  --renderer <NAME>    auto, half-block, quadrant, sextant, braille, ascii, kitty, sixel or iterm
+  --colors <MODE>      auto, truecolor, 256, 16 or mono
+  --dither <NAME>      fs, ordered or none
+  --filter <NAME>      auto, nearest, triangle, catmull-rom, gaussian, lanczos3 or area
+  --zoom <FACTOR>      initial magnification relative to --fit
+  --center <X,Y>       initial center in image pixels
+  --fit                show the whole image (default)
+  --fill               fill the window, cropping the image
+  --actual-size        one image pixel per terminal pixel
+  --background <COLOR> checker, black, white, gray or #rrggbb
+  --no-alt-screen      draw on the main screen instead of the alternate screen
+  --no-mouse           leave the mouse to the terminal, e.g. for selecting text
+  --watch              reload the image when its file changes
+  --slideshow <TIME>   advance to the next image every TIME, e.g. 5s, 500ms or 2m
+  --shuffle            show the images in random order
+  --loop               start the slideshow over after the last image
+  --print              render once to stdout and exit, without taking over the terminal
+  --width <COLS>       maximum width for --print (default: terminal width or 80)
+  --height <ROWS>      maximum height for --print (default: unlimited)
+  -h, --help           print this help
+  -V, --version        print the version";
+
+/// Settings given on the command line. Options left out are `None`
+/// so that the config file can supply them.
+pub struct Args {
+    pub paths: Vec<String>,
+    /// `Some(None)` for an explicit `auto`, which detects the renderer
+    /// from the terminal even when the config file names one.
+    pub renderer: Option<Option<Renderer>>,
+    /// `Some(None)` for an explicit `auto`, which detects the colour depth
+    /// from the environment even when the config file names one.
+    pub color_mode: Option<Option<ColorMode>>,
+    pub dither: Option<Dither>,
+    pub filter: Option<Filter>,
+    pub scale: Scale,
+    pub center: Option<(f64, f64)>,
+    pub background: Option<Background>,
+    pub alt_screen: bool,
+    /// Zoom with the wheel, drag to pan and double-click to reset.
+    pub mouse: bool,
+    pub watch: bool,
+    /// Interval of a slideshow started right away.
+    pub slideshow: Option<Duration>,
+    pub shuffle: bool,
+    pub looping: bool,
+    pub print: bool,
+    pub width: Option<u16>,
+    pub height: Option<u16>,
+}
+
+/// Parses the arguments, exiting for `--help`, `--version` and invalid input.
+pub fn parse(args: &[String]) -> Args {
+    parse_args(args).unwrap_or_else(|e| {
+        eprintln!("{}\nTry 'img_in_term --help' for more information.", e);
+        std::process::exit(2);
+    })
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut parsed = Args {
+        paths: Vec::new(),
+        renderer: None,
+        color_mode: None,
+        dither: None,
+        filter: None,
+        scale: Scale::Fit,
+        center: None,
+        background: None,
+        alt_screen: true,
+        mouse: true,
+        watch: false,
+        slideshow: None,
+        shuffle: false,
+        looping: false,
+        print: false,
+        width: None,
+        height: None,
+    };
+
+    let mut iter = args.iter().peekable();
+    if iter.next_if(|arg| *arg == "cat").is_some() {
+        parsed.print = true;
+    }
+    while let Some(arg) = iter.next() {
+        // options take their value either as `--name value` or `--name=value`
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| iter.next().cloned())
+                .ok_or(format!("option '{}' needs a value", name))
+        };
+
+        match name {
+            "-h" | "--help" => {
+                // ignore a closed pipe, e.g. `--help | head`
+                let _ = writeln!(stdout(), "{}", USAGE);
+                std::process::exit(0);
+            }
+            "-V" | "--version" => {
+                let _ = writeln!(stdout(), "img_in_term {}", env!("CARGO_PKG_VERSION"));
+                std::process::exit(0);
+            }
+            "--renderer" => {
+                parsed.renderer = Some(match value()?.as_str() {
+                    "auto" => None,
+                    name => Some(name.parse()?),
+                })
+            }
+            "--colors" => {
+                parsed.color_mode = Some(match value()?.as_str() {
+                    "auto" => None,
+                    name => Some(name.parse()?),
+                })
+            }
+            "--dither" => {
+                parsed.dither = Some(value()?.parse()?);
+            }
+            "--filter" => {
+                parsed.filter = Some(value()?.parse()?);
+            }
+            "--zoom" => {
+                let zoom = value()?;
+                match zoom.parse::<f64>() {
+                    Ok(magnify) if magnify >= 1.0 => parsed.scale = Scale::Zoom(magnify),
+                    _ => return Err(format!("invalid zoom '{}' (expected a number >= 1)", zoom)),
+                }
+            }
+            "--center" => {
+                let center = value()?;
+                let point = center
+                    .split_once(',')
+                    .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+                    .filter(|&(x, y): &(f64, f64)| x >= 0.0 && y >= 0.0);
+                match point {
+                    Some(point) => parsed.center = Some(point),
+                    None => return Err(format!("invalid center '{}' (expected X,Y)", center)),
+                }
+            }
+            "--fit" => parsed.scale = Scale::Fit,
+            "--fill" => parsed.scale = Scale::Fill,
+            "--actual-size" => parsed.scale = Scale::ActualSize,
+            "--background" => {
+                parsed.background = Some(value()?.parse()?);
+            }
+            "--no-alt-screen" => parsed.alt_screen = false,
+            "--no-mouse" => parsed.mouse = false,
+            "--watch" => parsed.watch = true,
+            "--slideshow" => {
+                let interval = value()?;
+                match parse_interval(&interval) {
+                    Some(interval) => parsed.slideshow = Some(interval),
+                    None => {
+                        return Err(format!(
+                            "invalid interval '{}' (expected e.g. 5s, 500ms or 2m)",
+                            interval
+                        ))
+                    }
+                }
+            }
+            "--shuffle" => parsed.shuffle = true,
+            "--loop" => parsed.looping = true,
+            "--print" => parsed.print = true,
+            "--width" => parsed.width = Some(parse_size(name, &value()?)?),
+            "--height" => parsed.height = Some(parse_size(name, &value()?)?),
+            "--" => parsed.paths.extend(iter.by_ref().cloned()),
+            // a lone `-` is a path, not an option
+            _ if name.starts_with('-') && name != "-" => {
+                return Err(format!("unknown option '{}'", name));
+            }
+            _ => parsed.paths.push(arg.clone()),
+        }
+    }
+
+    if parsed.paths.is_empty() {
+        return Err("no image given".to_string());
+    }
+    Ok(parsed)
+}
+
+/// Parses `5s`, `1.5s`, `500ms` or `2m`; a bare number is in seconds.
+fn parse_interval(text: &str) -> Option<Duration> {
+    let (number, scale) = if let Some(number) = text.strip_suffix("ms") {
+        (number, 0.001)
+    } else if let Some(number) = text.strip_suffix('s') {
+        (number, 1.0)
+    } else if let Some(number) = text.strip_suffix('m') {
+        (number, 60.0)
+    } else {
+        (text, 1.0)
+    };
+    let seconds = number.trim().parse::<f64>().ok()? * scale;
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|interval| *interval >= MIN_INTERVAL)
+}
+
+fn parse_size(name: &str, value: &str) -> Result<u16, String> {
+    match value.parse::<u16>() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(format!(
+            "invalid {} '{}' (expected a positive number of cells)",
+            &name[2..],
+            value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_list(args: &[&str]) -> Result<Args, String> {
+        parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    fn error(args: &[&str]) -> String {
+        match parse_list(args) {
+            Ok(_) => panic!("accepted {:?}", args),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn takes_values_both_ways() {
+        for args in [
+            &["--renderer", "sixel", "--width", "40", "a.png"][..],
+            &["--renderer=sixel", "--width=40", "a.png"][..],
+        ] {
+            let parsed = parse_list(args).unwrap();
+            assert_eq!(parsed.renderer, Some(Some(Renderer::Sixel)));
+            assert_eq!(parsed.width, Some(40));
+            assert_eq!(parsed.paths, ["a.png"]);
+        }
+        // an explicit `auto` is kept apart from a missing option
+        assert_eq!(
+            parse_list(&["--colors", "auto", "a.png"])
+                .unwrap()
+                .color_mode,
+            Some(None)
+        );
+        assert_eq!(parse_list(&["a.png"]).unwrap().color_mode, None);
+        // `=` only splits options
+        assert_eq!(parse_list(&["a=b.png"]).unwrap().paths, ["a=b.png"]);
+    }
+
+    #[test]
+    fn separates_paths_from_options() {
+        let parsed = parse_list(&["-", "--print", "--", "--fit", "-"]).unwrap();
+        assert!(parsed.print);
+        assert_eq!(parsed.paths, ["-", "--fit", "-"]);
+        assert_eq!(error(&["-x", "a.png"]), "unknown option '-x'");
+        assert_eq!(error(&["--print"]), "no image given");
+        assert_eq!(error(&["--"]), "no image given");
+    }
+
+    #[test]
+    fn cat_prints() {
+        let parsed = parse_list(&["cat", "a.png"]).unwrap();
+        assert!(parsed.print);
+        assert_eq!(parsed.paths, ["a.png"]);
+        // only as the first argument
+        let parsed = parse_list(&["a.png", "cat"]).unwrap();
+        assert!(!parsed.print);
+        assert_eq!(parsed.paths, ["a.png", "cat"]);
+    }
+
+    #[test]
+    fn reports_missing_values() {
+        assert_eq!(
+            error(&["a.png", "--renderer"]),
+            "option '--renderer' needs a value"
+        );
+        assert_eq!(
+            error(&["a.png", "--slideshow"]),
+            "option '--slideshow' needs a value"
+        );
+        assert!(error(&["--width=", "a.png"]).starts_with("invalid width ''"));
+    }
+
+    #[test]
+    fn checks_zoom_and_center() {
+        let parsed = parse_list(&["--zoom", "2.5", "--center", "10, 20", "a.png"]).unwrap();
+        assert_eq!(parsed.scale, Scale::Zoom(2.5));
+        assert_eq!(parsed.center, Some((10.0, 20.0)));
+        for zoom in ["0.5", "x", ""] {
+            assert_eq!(
+                error(&["--zoom", zoom, "a.png"]),
+                format!("invalid zoom '{}' (expected a number >= 1)", zoom)
+            );
+        }
+        for center in ["10", "-1,5", "a,b", "1,2,3"] {
+            assert_eq!(
+                error(&["--center", center, "a.png"]),
+                format!("invalid center '{}' (expected X,Y)", center)
+            );
+        }
+    }
+
+    #[test]
+    fn checks_sizes() {
+        assert_eq!(parse_size("--height", "12"), Ok(12));
+        assert_eq!(
+            parse_size("--height", "0"),
+            Err("invalid height '0' (expected a positive number of cells)".to_string())
+        );
+        assert!(parse_size("--width", "70000").is_err());
+        assert!(parse_size("--width", "-3").is_err());
+    }
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!(parse_interval("5"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_interval("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_interval("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_interval("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_interval("1ms"), Some(MIN_INTERVAL));
+        for text in [
+            "", "s", "0", "-1s", "1e-10s", "0.5ms", "1e300s", "inf", "nan", "5h",
+        ] {
+            assert_eq!(parse_interval(text), None, "{:?}", text);
+        }
+        assert_eq!(
+            error(&["--slideshow", "1e300s", "a.png"]),
+            "invalid interval '1e300s' (expected e.g. 5s, 500ms or 2m)"
+        );
+    }
+}
@@ -0,0 +1,35 @@
+use crossterm::style::Color;
+use image::RgbaImage;
+
+use crate::mod_palette::{to_color, ColorMode};
+use crate::mod_screen::{Cell, Screen};
+
+// characters from dark to bright, assuming light text on a dark background
+const RAMP: &[u8] = b" .:-=+*#%@";
+
+/// Draws the frame with one printable ASCII character per cell, each cell
+/// covering two vertically stacked pixels.
+pub fn draw(frame: &RgbaImage, screen: &mut Screen, cols: u16, rows: u16, color_mode: ColorMode) {
+    for y in 0..rows {
+        for x in 0..cols {
+            screen.set(x, y, cell(frame, x, y, color_mode));
+        }
+    }
+}
+
+fn cell(frame: &RgbaImage, x: u16, y: u16, color_mode: ColorMode) -> Cell {
+    let upper = frame.get_pixel(x as u32, y as u32 * 2);
+    let lower = frame.get_pixel(x as u32, y as u32 * 2 + 1);
+    let pixel = image::Rgba([0, 1, 2, 3].map(|c| ((upper[c] as u16 + lower[c] as u16) / 2) as u8));
+
+    let luma = (pixel[0] as usize * 299 + pixel[1] as usize * 587 + pixel[2] as usize * 114) / 1000;
+    Cell {
+        ch: RAMP[luma * (RAMP.len() - 1) / 255] as char,
+        fg: if color_mode == ColorMode::Mono {
+            Color::Reset
+        } else {
+            to_color(pixel, color_mode)
+        },
+        bg: Color::Reset,
+    }
+}
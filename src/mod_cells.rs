@@ -0,0 +1,121 @@
+use image::{Rgba, RgbaImage};
+
+use crate::mod_palette::{to_color, ColorMode};
+use crate::mod_screen::{Cell, Screen};
+
+/// Glyph families that split a cell into a grid of sub-pixels.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Glyphs {
+    Quadrant,
+    Sextant,
+    Braille,
+}
+
+impl Glyphs {
+    /// Sub-pixels per cell as (columns, rows).
+    pub fn cell_size(&self) -> (u32, u32) {
+        match self {
+            Glyphs::Quadrant => (2, 2),
+            Glyphs::Sextant => (2, 3),
+            Glyphs::Braille => (2, 4),
+        }
+    }
+
+    /// Glyph for a mask whose bit `y * 2 + x` is set when the sub-pixel
+    /// at (x, y) takes the foreground colour.
+    fn glyph(&self, mask: u8) -> char {
+        match self {
+            Glyphs::Quadrant => QUADRANTS[mask as usize],
+            Glyphs::Sextant => match mask {
+                0 => ' ',
+                21 => '\u{258c}',
+                42 => '\u{2590}',
+                63 => '\u{2588}',
+                // U+1FB00.. lists the remaining patterns in mask order
+                _ => {
+                    let skipped = (mask > 21) as u32 + (mask > 42) as u32;
+                    char::from_u32(0x1fb00 + mask as u32 - 1 - skipped).unwrap()
+                }
+            },
+            Glyphs::Braille => {
+                // braille numbers the dots column by column, with the last row appended
+                const DOTS: [u8; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+                let bits = (0..8)
+                    .filter(|i| mask & (1 << i) != 0)
+                    .fold(0, |bits, i| bits | DOTS[i]);
+                char::from_u32(0x2800 + bits as u32).unwrap()
+            }
+        }
+    }
+}
+
+const QUADRANTS: [char; 16] = [
+    ' ', '\u{2598}', '\u{259d}', '\u{2580}', '\u{2596}', '\u{258c}', '\u{259e}', '\u{259b}',
+    '\u{2597}', '\u{259a}', '\u{2590}', '\u{259c}', '\u{2584}', '\u{2599}', '\u{259f}', '\u{2588}',
+];
+
+/// Draws the frame with one glyph per cell, approximating each cell's
+/// sub-pixels with the two colours that best separate them.
+pub fn draw(
+    frame: &RgbaImage,
+    screen: &mut Screen,
+    cols: u16,
+    rows: u16,
+    glyphs: Glyphs,
+    color_mode: ColorMode,
+) {
+    let (cell_width, cell_height) = glyphs.cell_size();
+    for y in 0..rows {
+        for x in 0..cols {
+            let pixels = (0..cell_height)
+                .flat_map(|dy| (0..cell_width).map(move |dx| (dx, dy)))
+                .map(|(dx, dy)| {
+                    *frame.get_pixel(x as u32 * cell_width + dx, y as u32 * cell_height + dy)
+                })
+                .collect::<Vec<_>>();
+            let (mask, fg, bg) = split_colors(&pixels);
+
+            let cell = Cell {
+                ch: glyphs.glyph(mask),
+                fg: to_color(fg, color_mode),
+                bg: to_color(bg, color_mode),
+            };
+            screen.set(x, y, cell);
+        }
+    }
+}
+
+/// Splits the pixels into two groups along the channel with the widest range
+/// and returns the mask of the brighter group with the average colour of each group.
+fn split_colors(pixels: &[Rgba<u8>]) -> (u8, Rgba<u8>, Rgba<u8>) {
+    let (channel, min, max) = (0..3)
+        .map(|c| {
+            let min = pixels.iter().map(|p| p[c]).min().unwrap();
+            let max = pixels.iter().map(|p| p[c]).max().unwrap();
+            (c, min, max)
+        })
+        .max_by_key(|&(_, min, max)| max - min)
+        .unwrap();
+    let threshold = (min as u16 + max as u16).div_ceil(2) as u8;
+
+    let mut mask = 0;
+    let (mut fg, mut bg) = ([0u32; 4], [0u32; 4]);
+    for (i, p) in pixels.iter().enumerate() {
+        let sum = if max > min && p[channel] >= threshold {
+            mask |= 1 << i;
+            &mut fg
+        } else {
+            &mut bg
+        };
+        for c in 0..3 {
+            sum[c] += p[c] as u32;
+        }
+        sum[3] += 1;
+    }
+
+    let average = |sum: [u32; 4]| {
+        let channel = |c: usize| sum[c].checked_div(sum[3]).unwrap_or(0) as u8;
+        Rgba([channel(0), channel(1), channel(2), 255])
+    };
+    (mask, average(fg), average(bg))
+}
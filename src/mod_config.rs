@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::mod_display::{Background, Renderer};
+use crate::mod_keymap::{Action, KeyBinding, Keymap};
+use crate::mod_palette::{ColorMode, Dither};
+use crate::mod_pyramid::Filter;
+
+/// Settings from the config file. Command line options take precedence
+/// over the optional defaults.
+pub struct Config {
+    pub magnify_step: f64,
+    pub magnify_step_large: f64,
+    pub move_step_ratio: f64,
+    pub move_step_ratio_large: f64,
+    pub renderer: Option<Renderer>,
+    pub color_mode: Option<ColorMode>,
+    pub dither: Option<Dither>,
+    pub filter: Option<Filter>,
+    pub background: Option<Background>,
+    pub keymap: Keymap,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            magnify_step: 0.1,
+            magnify_step_large: 1.0,
+            move_step_ratio: 0.05,
+            move_step_ratio_large: 0.2,
+            renderer: None,
+            color_mode: None,
+            dither: None,
+            filter: None,
+            background: None,
+            keymap: Keymap::default(),
+        }
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// The loaded config, or the defaults if `init` was not called.
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+pub fn init(config: Config) {
+    // ignore a second init; the first config stays in use
+    let _ = CONFIG.set(config);
+}
+
+/// The config file as written, before validation.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    magnify_step: Option<f64>,
+    magnify_step_large: Option<f64>,
+    move_step_ratio: Option<f64>,
+    move_step_ratio_large: Option<f64>,
+    renderer: Option<String>,
+    colors: Option<String>,
+    dither: Option<String>,
+    filter: Option<String>,
+    background: Option<String>,
+    keys: HashMap<String, Keys>,
+}
+
+/// A single key or a list of keys.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// `$XDG_CONFIG_HOME/img_in_term/config.toml`, with `$XDG_CONFIG_HOME`
+/// defaulting to `~/.config`.
+fn path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("img_in_term").join("config.toml"))
+}
+
+/// Reads the config file. A missing file gives the defaults.
+pub fn load() -> Result<Config, String> {
+    let Some(path) = path() else {
+        return Ok(Config::default());
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e.trim_end()))
+}
+
+fn parse(text: &str) -> Result<Config, String> {
+    let file = toml::from_str::<ConfigFile>(text).map_err(|e| e.to_string())?;
+    let mut config = Config::default();
+
+    let steps = [
+        ("magnify_step", file.magnify_step, &mut config.magnify_step),
+        (
+            "magnify_step_large",
+            file.magnify_step_large,
+            &mut config.magnify_step_large,
+        ),
+        (
+            "move_step_ratio",
+            file.move_step_ratio,
+            &mut config.move_step_ratio,
+        ),
+        (
+            "move_step_ratio_large",
+            file.move_step_ratio_large,
+            &mut config.move_step_ratio_large,
+        ),
+    ];
+    for (name, value, step) in steps {
+        match value {
+            Some(value) if value > 0.0 && value.is_finite() => *step = value,
+            Some(value) => return Err(format!("{} must be positive, got {}", name, value)),
+            None => {}
+        }
+    }
+
+    config.renderer = match file.renderer.as_deref() {
+        None | Some("auto") => None,
+        Some(name) => Some(name.parse()?),
+    };
+    config.color_mode = match file.colors.as_deref() {
+        None | Some("auto") => None,
+        Some(name) => Some(name.parse()?),
+    };
+    if let Some(name) = file.dither {
+        config.dither = Some(name.parse()?);
+    }
+    if let Some(name) = file.filter {
+        config.filter = Some(name.parse()?);
+    }
+    if let Some(name) = file.background {
+        config.background = Some(name.parse()?);
+    }
+
+    for (name, keys) in file.keys {
+        let action =
+            Action::from_name(&name).ok_or(format!("[keys]: unknown action '{}'", name))?;
+        let keys = match keys {
+            Keys::One(key) => vec![key],
+            Keys::Many(keys) => keys,
+        };
+        let bindings = keys
+            .iter()
+            .map(|key| {
+                KeyBinding::parse(key).ok_or(format!(
+                    "[keys]: invalid key '{}' for action '{}'",
+                    key, name
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        config.keymap.bind(action, bindings);
+    }
+    if let Some((action, other)) = config.keymap.conflict() {
+        return Err(format!(
+            "[keys]: '{}' and '{}' share a key",
+            action.name(),
+            other.name()
+        ));
+    }
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mod_keymap::Step;
+
+    fn error(text: &str) -> String {
+        match parse(text) {
+            Ok(_) => panic!("accepted {:?}", text),
+            Err(e) => e,
+        }
+    }
+
+    fn key(text: &str) -> KeyBinding {
+        KeyBinding::parse(text).unwrap()
+    }
+
+    #[test]
+    fn empty_file_gives_defaults() {
+        let config = parse("").unwrap();
+        assert_eq!(config.magnify_step, 0.1);
+        assert_eq!(config.renderer, None);
+        assert_eq!(config.keymap.keys(Action::Quit), &[key("q")]);
+    }
+
+    #[test]
+    fn reads_settings() {
+        let config = parse(
+            "magnify_step = 0.5\nrenderer = \"braille\"\ncolors = \"auto\"\nfilter = \"area\"\nbackground = \"#ff8000\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.magnify_step, 0.5);
+        assert_eq!(config.renderer, Some(Renderer::Braille));
+        assert_eq!(config.color_mode, None);
+        assert_eq!(config.filter, Some(Filter::Area));
+        assert_eq!(
+            config.background,
+            Some(Background::Color(image::Rgba([255, 128, 0, 255])))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_steps() {
+        assert_eq!(
+            error("magnify_step = 0.0"),
+            "magnify_step must be positive, got 0"
+        );
+        assert_eq!(
+            error("move_step_ratio = -0.5"),
+            "move_step_ratio must be positive, got -0.5"
+        );
+        assert!(error("magnify_step_large = inf").contains("must be positive"));
+        assert!(error("move_step_ratio_large = nan").contains("must be positive"));
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        assert!(
+            error("renderer = \"fancy\"").starts_with("unknown renderer 'fancy' (expected auto,")
+        );
+        assert!(
+            error("filter = \"bicubic\"").starts_with("unknown filter 'bicubic' (expected auto,")
+        );
+        assert!(error("colors = \"8\"").starts_with("unknown color mode '8'"));
+        // misspelled settings are not silently ignored
+        assert!(error("magnify-step = 1.0").contains("magnify-step"));
+    }
+
+    #[test]
+    fn binds_one_key_or_a_list() {
+        let config = parse("[keys]\nquit = \"x\"\nopen = [\"ctrl+o\", \"O\"]\n").unwrap();
+        assert_eq!(config.keymap.keys(Action::Quit), &[key("x")]);
+        assert_eq!(config.keymap.keys(Action::Open), &[key("ctrl+o"), key("O")]);
+
+        // an empty list unbinds the action
+        let config = parse("[keys]\nquit = []\n").unwrap();
+        assert!(config.keymap.keys(Action::Quit).is_empty());
+
+        let config = parse("[keys]\nzoom_in_edge = \"z\"\n").unwrap();
+        assert_eq!(config.keymap.keys(Action::ZoomIn(Step::Edge)), &[key("z")]);
+    }
+
+    #[test]
+    fn rejects_bad_keys() {
+        assert_eq!(
+            error("[keys]\nfly = \"f\"\n"),
+            "[keys]: unknown action 'fly'"
+        );
+        assert_eq!(
+            error("[keys]\nquit = [\"q\", \"hyper+q\"]\n"),
+            "[keys]: invalid key 'hyper+q' for action 'quit'"
+        );
+        assert_eq!(
+            error("[keys]\nquit = \"ctrl+\"\n"),
+            "[keys]: invalid key 'ctrl+' for action 'quit'"
+        );
+        assert!(error("[keys]\nquit = 1\n").contains("quit"));
+    }
+
+    #[test]
+    fn rejects_shared_keys() {
+        // `n` is also the default key of next_image
+        assert_eq!(
+            error("[keys]\nquit = \"n\"\n"),
+            "[keys]: 'next_image' and 'quit' share a key"
+        );
+        // keys of two remapped actions
+        assert_eq!(
+            error("[keys]\nopen = \"x\"\nreset = \"x\"\n"),
+            "[keys]: 'reset' and 'open' share a key"
+        );
+        // a key that moved away no longer conflicts
+        assert!(
+            parse("[keys]\nquit = \"n\"\nnext_image = \"N\"\nprevious_image = \"P\"\n").is_ok()
+        );
+    }
+}
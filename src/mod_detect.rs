@@ -0,0 +1,92 @@
+use std::env;
+use std::io::{stdout, Write};
+use std::time::Duration;
+
+use crossterm::execute;
+use crossterm::style::Print;
+
+use crate::mod_display::Renderer;
+use crate::mod_events::read_reply;
+use crate::mod_palette::ColorMode;
+
+// how long to wait for the terminal to answer the capability queries
+const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+// a 1x1 RGB image that is queried but never stored or displayed
+const KITTY_QUERY: &str = "\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\";
+// primary device attributes, answered by virtually every terminal
+const DA1_QUERY: &str = "\x1b[c";
+
+/// Picks the best renderer the terminal supports.
+/// Must be called while raw mode is enabled.
+pub fn detect_renderer() -> Renderer {
+    // multiplexers answer queries themselves and drop graphics sequences
+    if env::var_os("TMUX").is_some() || env_contains("TERM", "screen") {
+        return Renderer::HalfBlock;
+    }
+
+    let (kitty, sixel) = query_terminal();
+    if kitty || env_contains("TERM", "kitty") || env_is("TERM_PROGRAM", "ghostty") {
+        Renderer::Kitty
+    } else if env_is("TERM_PROGRAM", "iTerm.app")
+        || env_is("TERM_PROGRAM", "WezTerm")
+        || env_is("LC_TERMINAL", "iTerm2")
+        || env::var_os("KONSOLE_VERSION").is_some()
+    {
+        Renderer::Iterm
+    } else if sixel || env_contains("TERM", "sixel") || env_contains("TERM", "mlterm") {
+        Renderer::Sixel
+    } else {
+        Renderer::HalfBlock
+    }
+}
+
+/// Guesses the colour depth of the terminal from `$COLORTERM` and `$TERM`.
+pub fn detect_color_mode() -> ColorMode {
+    if env_is("COLORTERM", "truecolor")
+        || env_is("COLORTERM", "24bit")
+        || env::var_os("WT_SESSION").is_some()
+    {
+        return ColorMode::TrueColor;
+    }
+    match env::var("TERM") {
+        Ok(term) if term.contains("truecolor") || term.contains("direct") => ColorMode::TrueColor,
+        Ok(term) if term.contains("256") => ColorMode::Ansi256,
+        Ok(term) if term == "dumb" || term.starts_with("vt") => ColorMode::Mono,
+        Ok(_) => ColorMode::Ansi16,
+        // no $TERM usually means a Windows console, which handles 24-bit colour
+        Err(_) => ColorMode::TrueColor,
+    }
+}
+
+/// Sends the kitty graphics query followed by DA1 and returns whether
+/// the terminal supports (kitty graphics, sixel).
+fn query_terminal() -> (bool, bool) {
+    execute!(stdout(), Print(KITTY_QUERY), Print(DA1_QUERY)).unwrap();
+    stdout().flush().unwrap();
+
+    // the DA1 answer `ESC [ ? Ps ; ... c` comes last since terminals reply in order
+    let reply = read_reply(QUERY_TIMEOUT, |reply| da1_params(reply).is_some());
+    let reply = String::from_utf8_lossy(&reply);
+
+    let kitty = reply.contains("\x1b_Gi=31;OK");
+    let sixel = da1_params(reply.as_bytes())
+        .map(|params| params.split(';').any(|p| p == "4"))
+        .unwrap_or(false);
+    (kitty, sixel)
+}
+
+fn da1_params(reply: &[u8]) -> Option<String> {
+    let reply = String::from_utf8_lossy(reply);
+    let start = reply.find("\x1b[?")? + 3;
+    let len = reply[start..].find('c')?;
+    Some(reply[start..start + len].to_string())
+}
+
+fn env_is(key: &str, value: &str) -> bool {
+    env::var(key).map(|v| v == value).unwrap_or(false)
+}
+
+fn env_contains(key: &str, value: &str) -> bool {
+    env::var(key).map(|v| v.contains(value)).unwrap_or(false)
+}
@@ -1,10 +1,199 @@
-use std::io::{stdout, Write};
+use std::io::{stdout, Cursor, Write};
+use std::str::FromStr;
+use std::time::Duration;
 
-use crossterm::cursor::MoveTo;
-use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
-use crossterm::{queue, terminal};
-use image::{DynamicImage, GenericImageView, ImageError, RgbaImage};
-use image::{GenericImage, Rgba};
+use crossterm::style::Color;
+use crossterm::terminal;
+use image::Rgba;
+use image::{ImageOutputFormat, RgbaImage};
+
+use crate::mod_animation::Animation;
+use crate::mod_cells::Glyphs;
+use crate::mod_config::config;
+use crate::mod_keymap::{Action, Step};
+use crate::mod_loader::ImageState;
+use crate::mod_palette::{hsv, quantize, to_color, ColorMode, Dither};
+use crate::mod_playlist::Slideshow;
+use crate::mod_pyramid::{Filter, Pyramid};
+use crate::mod_screen::{Cell, Screen};
+use crate::{mod_ascii, mod_cells, mod_iterm, mod_kitty, mod_sixel};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Renderer {
+    HalfBlock,
+    Quadrant,
+    Sextant,
+    Braille,
+    Ascii,
+    Kitty,
+    Sixel,
+    Iterm,
+}
+
+impl Renderer {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Renderer::HalfBlock => "half-block",
+            Renderer::Quadrant => "quadrant",
+            Renderer::Sextant => "sextant",
+            Renderer::Braille => "braille",
+            Renderer::Ascii => "ascii",
+            Renderer::Kitty => "kitty",
+            Renderer::Sixel => "sixel",
+            Renderer::Iterm => "iterm",
+        }
+    }
+
+    pub fn next(&self) -> Renderer {
+        match self {
+            Renderer::HalfBlock => Renderer::Quadrant,
+            Renderer::Quadrant => Renderer::Sextant,
+            Renderer::Sextant => Renderer::Braille,
+            Renderer::Braille => Renderer::Ascii,
+            Renderer::Ascii => Renderer::Kitty,
+            Renderer::Kitty => Renderer::Sixel,
+            Renderer::Sixel => Renderer::Iterm,
+            Renderer::Iterm => Renderer::HalfBlock,
+        }
+    }
+
+    /// Whether the renderer sends real pixels through a terminal graphics protocol.
+    pub fn is_graphics(&self) -> bool {
+        matches!(self, Renderer::Kitty | Renderer::Sixel | Renderer::Iterm)
+    }
+
+    /// Pixels per cell as (columns, rows); real pixels for the graphics protocols.
+    fn cell_size(&self) -> (u32, u32) {
+        match self.glyphs() {
+            _ if self.is_graphics() => cell_pixel_size(),
+            Some(glyphs) => glyphs.cell_size(),
+            None => (1, 2),
+        }
+    }
+
+    fn glyphs(&self) -> Option<Glyphs> {
+        match self {
+            Renderer::Quadrant => Some(Glyphs::Quadrant),
+            Renderer::Sextant => Some(Glyphs::Sextant),
+            Renderer::Braille => Some(Glyphs::Braille),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Renderer {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Renderer, String> {
+        match name {
+            "half-block" => Ok(Renderer::HalfBlock),
+            "quadrant" => Ok(Renderer::Quadrant),
+            "sextant" => Ok(Renderer::Sextant),
+            "braille" => Ok(Renderer::Braille),
+            "ascii" => Ok(Renderer::Ascii),
+            "kitty" => Ok(Renderer::Kitty),
+            "sixel" => Ok(Renderer::Sixel),
+            "iterm" => Ok(Renderer::Iterm),
+            _ => Err(format!(
+                "unknown renderer '{}' (expected auto, half-block, quadrant, sextant, braille, ascii, kitty, sixel or iterm)",
+                name
+            )),
+        }
+    }
+}
+
+/// How the view is zoomed when an image is shown or the view is reset.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Scale {
+    /// The whole image is visible.
+    Fit,
+    /// The image covers the whole window, cropping the overflowing side.
+    Fill,
+    /// One image pixel per terminal pixel (or per sub-pixel of text renderers).
+    ActualSize,
+    /// Magnification relative to `Fit`.
+    Zoom(f64),
+}
+
+/// What transparent parts of the image are blended over.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Background {
+    Checker,
+    Color(Rgba<u8>),
+}
+
+impl FromStr for Background {
+    type Err = String;
+
+    /// Parses `checker`, `black`, `white`, `gray` or a `#rrggbb` colour.
+    fn from_str(name: &str) -> Result<Background, String> {
+        let hex = |name: &str| {
+            let hex = name.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+            let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+            Some([channel(0)?, channel(2)?, channel(4)?])
+        };
+        let rgb = match name {
+            "checker" => return Ok(Background::Checker),
+            "black" => [0, 0, 0],
+            "white" => [255, 255, 255],
+            "gray" | "grey" => [128, 128, 128],
+            _ => hex(name).ok_or(format!(
+                "unknown background '{}' (expected checker, black, white, gray or #rrggbb)",
+                name
+            ))?,
+        };
+        Ok(Background::Color(Rgba([rgb[0], rgb[1], rgb[2], 255])))
+    }
+}
+
+/// How the pixels of a drawn frame relate to the image and the terminal.
+/// The frame shows a whole-pixel region of the image, which may be
+/// scaled a little differently than the fractional `clip_size` suggests.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Mapping {
+    /// Drawn region of the image, `(l, t, w, h)` in image pixels.
+    pub region: (u32, u32, u32, u32),
+    /// Where the region landed, `(x, y, w, h)` in frame pixels.
+    pub placement: (u32, u32, u32, u32),
+    /// Frame pixels per terminal cell.
+    pub cell_size: (u32, u32),
+    /// Size of the whole frame in pixels.
+    pub frame_size: (u32, u32),
+}
+
+impl Mapping {
+    /// Frame pixels per image pixel.
+    pub fn scale(&self) -> (f64, f64) {
+        (
+            self.placement.2 as f64 / self.region.2 as f64,
+            self.placement.3 as f64 / self.region.3 as f64,
+        )
+    }
+
+    pub fn image_to_frame(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let scale = self.scale();
+        (
+            self.placement.0 as f64 + (x - self.region.0 as f64) * scale.0,
+            self.placement.1 as f64 + (y - self.region.1 as f64) * scale.1,
+        )
+    }
+
+    pub fn frame_to_image(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let scale = self.scale();
+        (
+            self.region.0 as f64 + (x - self.placement.0 as f64) / scale.0,
+            self.region.1 as f64 + (y - self.placement.1 as f64) / scale.1,
+        )
+    }
+
+    /// Image coordinates at the middle of cell (`col`, `row`).
+    pub fn cell_to_image(&self, col: u16, row: u16) -> (f64, f64) {
+        self.frame_to_image((
+            (col as f64 + 0.5) * self.cell_size.0 as f64,
+            (row as f64 + 0.5) * self.cell_size.1 as f64,
+        ))
+    }
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct DisplayInfo {
@@ -14,247 +203,679 @@ pub struct DisplayInfo {
     pub clip_size: (f64, f64),
     pub img_size: (u32, u32),
     pub show_help: bool,
+    /// Where the last frame put the image, `None` before it is drawn.
+    pub mapping: Option<Mapping>,
+    /// Image pixel under the crosshair in inspect mode.
+    pub inspect: Option<(u32, u32)>,
+    /// Reload the image when its file changes.
+    pub watch: bool,
+    /// Short message shown in place of the help hint, e.g. after a reload.
+    pub notice: Option<String>,
+    pub slideshow: Slideshow,
+    pub renderer: Renderer,
+    pub color_mode: ColorMode,
+    pub dither: Dither,
+    pub filter: Filter,
+    /// 1-based index of the image in the playlist and the playlist length.
+    pub position: (usize, usize),
+    /// Zoom applied when the view is reset.
+    pub scale: Scale,
+    /// Center applied when the view is reset, the image center if `None`.
+    pub start_center: Option<(f64, f64)>,
+    pub background: Background,
 }
 
-pub fn display(image: &Result<DynamicImage, ImageError>, info: &mut DisplayInfo) {
-    // clear terminal
-    queue!(stdout(), terminal::Clear(terminal::ClearType::All)).unwrap();
-
+pub fn display(image: &ImageState, info: &mut DisplayInfo, screen: &mut Screen) {
     // get terminal size
-    if image.is_ok() {
-        // get terminal size
-        let (term_width, term_height) = terminal::size().unwrap();
-
-        // calculate window_size
-        let (win_width, win_height) = (term_width, (term_height - 1) * 2);
-        let (win_width, win_height) = (win_width as u32, win_height as u32);
-
-        // create background
-        let bg_color_light = Rgba([153, 153, 153, 0]);
-        let bg_color_dark = Rgba([102, 102, 102, 0]);
-        let mut bg = DynamicImage::ImageRgba8(RgbaImage::new(win_width, win_height));
-        for y in 0..win_height {
-            for x in 0..win_width {
-                let bg_color = if (x + y) % 2 == 0 {
-                    bg_color_light
-                } else {
-                    bg_color_dark
-                };
-                bg.put_pixel(x, y, bg_color);
-            }
-        }
+    let (term_width, term_height) = terminal::size().unwrap();
+    let graphics = matches!(image, ImageState::Ready(_)) && info.renderer.is_graphics();
 
-        // load, resize and clip image
-        let img = image.as_ref().unwrap();
-        let img = if info.center.0 < 0.0 || info.center.1 < 0.0 {
-            // if default size
-            info.center = (img.width() as f64 / 2.0, img.height() as f64 / 2.0);
-            info.clip_size = (img.width() as f64, img.height() as f64);
-            info.img_size = (img.width(), img.height());
-            img.resize(win_width, win_height, image::imageops::FilterType::Nearest)
-        } else {
-            // if clipping needed
-            let (img_width, img_height) = img.dimensions();
-            let (img_width, img_height) = (img_width as f64, img_height as f64);
-            let (clip_width, clip_height) =
-                if img_height / img_width > win_height as f64 / win_width as f64 {
-                    // fit height
-                    (
-                        img_height / win_height as f64 * win_width as f64,
-                        img_height,
-                    )
-                } else {
-                    // fit width
-                    (img_width, img_width / win_width as f64 * win_height as f64)
-                };
-            let (clip_width, clip_height) = (clip_width / info.magnify, clip_height / info.magnify);
-            info.clip_size = (clip_width, clip_height);
-
-            let (l, t) = (
-                (info.center.0 - clip_width / 2.0) as u32,
-                (info.center.1 - clip_height / 2.0) as u32,
+    // start a new frame; images of the graphics protocols need a full redraw,
+    // also when they are replaced by text
+    mod_kitty::clear();
+    screen.begin(term_width, term_height, graphics);
+    // the status line is also written by the input box and error messages
+    screen.invalidate_row(term_height - 1);
+
+    match image {
+        ImageState::Ready(animation) => {
+            draw_image(animation, info, screen, term_width, term_height)
+        }
+        ImageState::Loading(loader) => {
+            let status = format!(
+                "{}loading {} {} ({:.1}s)",
+                position_label(info),
+                info.image_file_path,
+                loader.spinner(),
+                loader.elapsed().as_secs_f64()
+            );
+            let hint = [
+                (&[Action::Cancel][..], "cancel"),
+                (&[Action::NextImage, Action::PreviousImage][..], "skip"),
+                (&[Action::Quit][..], "exit"),
+            ]
+            .into_iter()
+            .filter_map(|(actions, description)| {
+                let keys: Vec<_> = actions
+                    .iter()
+                    .filter_map(|action| first_key(*action))
+                    .collect();
+                (!keys.is_empty()).then(|| format!("{}: {}", keys.join("/"), description))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+            screen.print(0, term_height - 1, &status, Color::Reset, Color::Reset);
+            screen.print(
+                term_width.saturating_sub(hint.len() as u16),
+                term_height - 1,
+                &hint,
+                Color::Reset,
+                Color::Reset,
             );
-            let mut img = img.clone();
-            img.crop(l, t, clip_width as u32, clip_height as u32)
-                .resize(win_width, win_height, image::imageops::FilterType::Nearest)
-        };
-        let (img_width, img_height) = img.dimensions();
-
-        // create buffer
-        let mut buffer = DynamicImage::ImageRgba8(RgbaImage::new(win_width, win_height));
-
-        let (anchor_x, anchor_y) = ((win_width - img_width) / 2, (win_height - img_height) / 2);
-        for y in 0..img_height {
-            for x in 0..img_width {
-                let pixel = img.get_pixel(x, y);
-                buffer.put_pixel(x + anchor_x, y + anchor_y, pixel);
-
-                let bg_pixel = bg.get_pixel(x + anchor_x, y + anchor_y);
-                bg.put_pixel(
-                    x + anchor_x,
-                    y + anchor_y,
-                    Rgba([bg_pixel[0], bg_pixel[1], bg_pixel[2], 255]),
-                );
-            }
         }
-
-        // display image in terminal from buffer
-        for y in 0..(term_height - 1) {
-            for x in 0..term_width {
-                let (x, y) = (x as u32, y as u32);
-                let true_y = y * 2;
-
-                let upper_pixel = buffer.get_pixel(x, true_y);
-                let upper_bg = bg.get_pixel(x, true_y);
-                let upper_color = blend(upper_pixel, upper_bg);
-
-                let lower_pixel = buffer.get_pixel(x, true_y + 1);
-                let lower_bg = bg.get_pixel(x, true_y + 1);
-                let lower_color = blend(lower_pixel, lower_bg);
-
-                queue!(
-                    stdout(),
-                    MoveTo(x as u16, y as u16),
-                    SetForegroundColor(upper_color),
-                    SetBackgroundColor(lower_color),
-                    Print("\u{2580}"),
-                    ResetColor,
-                )
-                .unwrap();
+        ImageState::Failed(err) => {
+            // Image open error
+            let choices: Vec<_> = [(Action::Open, "type file path"), (Action::Quit, "exit")]
+                .into_iter()
+                .filter_map(|(action, description)| {
+                    first_key(action).map(|key| format!("'{}' to {}", key, description))
+                })
+                .collect();
+            let mut lines = vec![
+                format!("Error: {}", err),
+                format!("Image path: {}", info.image_file_path),
+            ];
+            if !choices.is_empty() {
+                lines.push(format!("Press {}.", choices.join(" or ")));
+            }
+            for (i, line) in lines.iter().enumerate() {
+                let y = (term_height + i as u16).saturating_sub(3);
+                screen.print(0, y, line, Color::Reset, Color::Reset);
             }
         }
+    }
+    screen.flush();
+    stdout().flush().unwrap();
+}
 
-        // show help
-        if info.show_help {
-            let version = env!("CARGO_PKG_VERSION");
-
-            let title = format!("Image In Terminal - v{}", version);
-            let msgs = [
-                "w/W: zoom in".to_string(),
-                "s/S: zoom out".to_string(),
-                "h/H: move left".to_string(),
-                "l/L: move right".to_string(),
-                "j/J: move down".to_string(),
-                "k/K: move up".to_string(),
-                " y : hide help".to_string(),
-                " o : open image".to_string(),
-                " q : exit".to_string(),
-            ];
+fn draw_image(
+    animation: &Animation,
+    info: &mut DisplayInfo,
+    screen: &mut Screen,
+    term_width: u16,
+    term_height: u16,
+) {
+    let img = animation.image();
+    let (cols, rows) = (term_width, term_height - 1);
 
-            let (mut w2, h) = (0, msgs.len() as u16 + 2);
-            for msg in &msgs {
-                w2 = w2.max(msg.len() as u16);
-            }
-            let w1 = w2.max(title.len() as u16);
+    // calculate window_size
+    let (cell_width, cell_height) = info.renderer.cell_size();
+    let (win_width, win_height) = (cols as u32 * cell_width, rows as u32 * cell_height);
 
-            let (anchor_help_w, anchor_help_h) = (
-                (term_width - w1 - 4) / 2,
-                (term_height - h - 2) / 2,
-            );
+    // load, resize and clip image
+    let mut frame = compose(img, info, win_width, win_height, (cell_width, cell_height));
+    if info.inspect.is_some() {
+        keep_cursor_visible(info);
+        draw_crosshair(&mut frame, info);
+    }
 
-            let help_padding = (w1 - w2) / 2;
-
-            // show border
-            for x in 0..(w1 + 4) {
-                queue!(
-                    stdout(),
-                    MoveTo(anchor_help_w + x, anchor_help_h),
-                    ResetColor,
-                    Print(if x == 0 || x == w1 + 3 { "\u{2588}" } else { "\u{2580}" }),
-                )
-                .unwrap();
-            }
+    // display image in terminal from frame
+    match info.renderer {
+        Renderer::Kitty => mod_kitty::draw(&frame, cols, rows),
+        Renderer::Sixel => mod_sixel::draw(&frame),
+        Renderer::Iterm => mod_iterm::draw(&frame, cols, rows),
+        _ => draw_cells(&mut frame, info, screen, cols, rows),
+    }
 
-            for y in 0..h {
-                for x in 0..(w1 + 4) {
-                    queue!(
-                        stdout(),
-                        MoveTo(anchor_help_w + x, anchor_help_h+1 + y),
-                        ResetColor,
-                        Print(if x == 0 || x == w1 + 3 { "\u{2588}" } else { " " }),
-                    )
-                    .unwrap();
-                }
-            }
-            for x in 0..(w1 + 4) {
-                queue!(
-                    stdout(),
-                    MoveTo(anchor_help_w + x, anchor_help_h+h+1),
-                    ResetColor,
-                    Print(if x == 0 || x == w1 + 3 { "\u{2588}" } else { "\u{2584}" }),
-                )
-                .unwrap();
-            }
-            // show title
-            queue!(
-                stdout(),
-                MoveTo(anchor_help_w, anchor_help_h + 1),
-                Print("\u{2588} "),
-                MoveTo(anchor_help_w+2,  anchor_help_h + 1),
-                Print(&title),
-                MoveTo(anchor_help_w + 2 + w1,  anchor_help_h + 1),
-                Print(" \u{2588}"),
+    // show help
+    if info.show_help {
+        draw_help(
+            screen,
+            term_width,
+            term_height,
+            info.renderer == Renderer::Ascii,
+        );
+    }
+
+    let show_hint_msg = first_key(Action::ToggleHelp)
+        .map(|key| {
+            let verb = if info.show_help { "hide" } else { "show" };
+            format!("Press '{}' to {} help", key, verb)
+        })
+        .unwrap_or_default();
+    let status = match info.inspect {
+        Some((x, y)) => {
+            let pixel = img.pixel(x, y);
+            let (hue, saturation, value) = hsv([pixel[0], pixel[1], pixel[2]]);
+            format!(
+                "{}x: {}, y: {}, rgba: ({}, {}, {}, {}), hex: #{:02x}{:02x}{:02x}{}, hsv: ({:.0}, {:.0}%, {:.0}%)",
+                position_label(info),
+                x,
+                y,
+                pixel[0],
+                pixel[1],
+                pixel[2],
+                pixel[3],
+                pixel[0],
+                pixel[1],
+                pixel[2],
+                // the alpha digits only for translucent pixels
+                if pixel[3] < 255 {
+                    format!("{:02x}", pixel[3])
+                } else {
+                    String::new()
+                },
+                hue,
+                saturation * 100.0,
+                value * 100.0
             )
-            .unwrap();
+        }
+        None => format!(
+            "{}magnify: x{:.2}, center: ({:.2}, {:.2}), renderer: {}{}, filter: {}",
+            position_label(info),
+            info.magnify,
+            info.center.0,
+            info.center.1,
+            info.renderer.name(),
+            if !info.renderer.is_graphics() && info.color_mode != ColorMode::TrueColor {
+                format!(" ({})", info.color_mode.name())
+            } else {
+                String::new()
+            },
+            info.filter.name()
+        ),
+    } + &animation_label(animation)
+        + if info.watch { ", watching" } else { "" }
+        + &slideshow_label(&info.slideshow);
+    screen.print(0, term_height - 1, &status, Color::Reset, Color::Reset);
+    if let Some(notice) = &info.notice {
+        // notices are short-lived, so they may cover the end of the status
+        let notice = format!(" {}", notice);
+        screen.print(
+            term_width.saturating_sub(notice.len() as u16),
+            term_height - 1,
+            &notice,
+            Color::Reset,
+            Color::Reset,
+        );
+    } else if status.len() + show_hint_msg.len() < term_width as usize {
+        // the hint gives way to the status on narrow terminals
+        screen.print(
+            term_width - show_hint_msg.len() as u16,
+            term_height - 1,
+            &show_hint_msg,
+            Color::Reset,
+            Color::Reset,
+        );
+    }
+}
 
-            // show help message
+// "3/47 " prefix of the status line, empty for a single image
+fn position_label(info: &DisplayInfo) -> String {
+    match info.position {
+        (_, total) if total <= 1 => String::new(),
+        (index, total) => format!("{}/{} ", index, total),
+    }
+}
 
-            for (i, msg) in msgs.iter().enumerate() {
-                queue!(
-                    stdout(),
-                    MoveTo(anchor_help_w + 2 + help_padding, anchor_help_h + 3 + i as u16),
-                    Print(msg),
-                )
-                .unwrap();
-            }
+// ", slideshow: 5s (paused)" suffix of the status line, empty without a slideshow
+fn slideshow_label(slideshow: &Slideshow) -> String {
+    if !slideshow.active {
+        return String::new();
+    }
+    format!(
+        ", slideshow: {}{}",
+        format_interval(slideshow.interval),
+        if slideshow.paused { " (paused)" } else { "" }
+    )
+}
+
+/// `5s`, `1.5s` or `500ms`.
+pub fn format_interval(interval: Duration) -> String {
+    if interval < Duration::from_secs(1) {
+        format!("{}ms", interval.as_millis())
+    } else {
+        format!("{}s", interval.as_secs_f64())
+    }
+}
+
+// ", frame: 3/12 (x2, paused)" suffix of the status line, empty for still images
+fn animation_label(animation: &Animation) -> String {
+    if !animation.is_animated() {
+        return String::new();
+    }
+    let (index, total) = animation.position();
+    let mut state = Vec::new();
+    if animation.speed() != 1.0 {
+        state.push(format!("x{}", animation.speed()));
+    }
+    if animation.is_paused() {
+        state.push("paused".to_string());
+    }
+    if state.is_empty() {
+        format!(", frame: {}/{}", index, total)
+    } else {
+        format!(", frame: {}/{} ({})", index, total, state.join(", "))
+    }
+}
+
+/// Moves the inspect cursor into the drawn region of the image,
+/// e.g. after panning or switching images.
+fn keep_cursor_visible(info: &mut DisplayInfo) {
+    let (Some((x, y)), Some(mapping)) = (info.inspect, info.mapping) else {
+        return;
+    };
+    let (l, t, w, h) = mapping.region;
+    info.inspect = Some((x.clamp(l, l + w - 1), y.clamp(t, t + h - 1)));
+}
+
+/// Inverts a row and a column of the frame through the inspected pixel,
+/// leaving a gap of about one cell around it so that it stays visible.
+fn draw_crosshair(frame: &mut RgbaImage, info: &DisplayInfo) {
+    let (Some((x, y)), Some(mapping)) = (info.inspect, info.mapping) else {
+        return;
+    };
+    let (width, height) = frame.dimensions();
+    let scale = mapping.scale();
+    let middle = mapping.image_to_frame((x as f64 + 0.5, y as f64 + 0.5));
+    let gap = (
+        scale.0 / 2.0 + mapping.cell_size.0 as f64,
+        scale.1 / 2.0 + mapping.cell_size.1 as f64,
+    );
+    let (mx, my) = (middle.0 as u32, middle.1 as u32);
+    if mx >= width || my >= height {
+        return;
+    }
+
+    let invert = |pixel: &mut Rgba<u8>| {
+        *pixel = Rgba([255 - pixel[0], 255 - pixel[1], 255 - pixel[2], 255]);
+    };
+    for fx in 0..width {
+        if (fx as f64 + 0.5 - middle.0).abs() > gap.0 {
+            invert(frame.get_pixel_mut(fx, my));
+        }
+    }
+    for fy in 0..height {
+        if (fy as f64 + 0.5 - middle.1).abs() > gap.1 {
+            invert(frame.get_pixel_mut(mx, fy));
         }
+    }
+}
 
-        let show_hint_msg = format!(
-            "Press 'y' to {} help",
-            if info.show_help { "hide" } else { "show" }
-        );
-        queue!(
-            stdout(),
-            MoveTo(0, term_height - 1),
-            Print(format!(
-                "magnify: x{:.2}, center: ({:.2}, {:.2})",
-                info.magnify, info.center.0, info.center.1
-            )),
-            MoveTo(term_width - (show_hint_msg.len() as u16), term_height - 1),
-            Print(show_hint_msg),
-            MoveTo(0, term_height - 1),
-        )
+/// Renders the image once to stdout at the cursor, without clearing the screen.
+/// The image is as large as fits in `max_cols` x `max_rows` cells.
+pub fn print(image: &Pyramid, info: &mut DisplayInfo, max_cols: u16, max_rows: Option<u16>) {
+    let (img_width, img_height) = image.dimensions();
+    let (cell_width, cell_height) = info.renderer.cell_size();
+    // cells needed to show `cols` image columns without distortion
+    let rows_for = |cols: u16| {
+        (cols as f64 * cell_width as f64 * img_height as f64
+            / img_width as f64
+            / cell_height as f64)
+            .round()
+            .max(1.0) as u16
+    };
+    let (mut cols, mut rows) = (max_cols.max(1), rows_for(max_cols.max(1)));
+    if let Some(max_rows) = max_rows.filter(|&max_rows| rows > max_rows) {
+        rows = max_rows.max(1);
+        cols = ((rows as f64 * cell_height as f64 * img_width as f64
+            / img_height as f64
+            / cell_width as f64)
+            .round() as u16)
+            .clamp(1, cols);
+    }
+
+    let (win_width, win_height) = (cols as u32 * cell_width, rows as u32 * cell_height);
+    let mut frame = compose(
+        image,
+        info,
+        win_width,
+        win_height,
+        (cell_width, cell_height),
+    );
+    let out = match info.renderer {
+        Renderer::Kitty => mod_kitty::inline(&frame, cols, rows) + "\n",
+        Renderer::Sixel => mod_sixel::encode(&frame) + "\n",
+        Renderer::Iterm => mod_iterm::sequence(&frame, cols, rows, true) + "\n",
+        _ => {
+            let mut screen = Screen::new(cols, rows);
+            draw_cells(&mut frame, info, &mut screen, cols, rows);
+            (0..rows).map(|y| screen.line(y) + "\n").collect()
+        }
+    };
+
+    // stop quietly when the reader goes away, e.g. `| head`
+    let mut stdout = stdout();
+    stdout
+        .write_all(out.as_bytes())
+        .and_then(|_| stdout.flush())
+        .ok();
+}
+
+/// Size of a terminal cell in pixels, falling back to a common 10x20 cell
+/// when the terminal does not report its pixel size.
+pub fn cell_pixel_size() -> (u32, u32) {
+    match terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns).max(1) as u32,
+            (size.height / size.rows).max(1) as u32,
+        ),
+        _ => (10, 20),
+    }
+}
+
+/// Encodes a composed frame as PNG for the graphics protocol renderers.
+pub fn encode_png(frame: &RgbaImage) -> Vec<u8> {
+    let mut png = Vec::new();
+    frame
+        .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
         .unwrap();
-        stdout().flush().unwrap();
-    } else {
-        let (_, term_height) = terminal::size().unwrap();
-
-        // Image open error
-        let err = image.as_ref().unwrap_err();
-        queue!(
-            stdout(),
-            MoveTo(0, term_height - 3),
-            Print(format!("Error: {}", err)),
-            MoveTo(0, term_height - 2),
-            Print(format!("Image path: {}", info.image_file_path)),
-            MoveTo(0, term_height - 1),
-            Print("Press 'o' to type file path or 'q' to exit."),
-            MoveTo(0, term_height - 1),
+    png
+}
+
+/// Clips and resizes the image into a `win_width` x `win_height` frame
+/// of cells of `cell_size` pixels, blended over a checkerboard with
+/// squares of half a cell. Pixels not covered by the image are left
+/// fully transparent. Records where the image went in `info.mapping`.
+fn compose(
+    img: &Pyramid,
+    info: &mut DisplayInfo,
+    win_width: u32,
+    win_height: u32,
+    cell_size: (u32, u32),
+) -> RgbaImage {
+    let checker = (cell_size.1 / 2).max(1);
+    info.img_size = img.dimensions();
+    let (img_width, img_height) = img.dimensions();
+    let (img_width, img_height) = (img_width as f64, img_height as f64);
+    let (fit_width, fit_height) = if img_height / img_width > win_height as f64 / win_width as f64 {
+        // fit height
+        (
+            img_height / win_height as f64 * win_width as f64,
+            img_height,
         )
-        .unwrap();
-        stdout().flush().unwrap();
+    } else {
+        // fit width
+        (img_width, img_width / win_width as f64 * win_height as f64)
+    };
+
+    // reset view; zooming out beyond fit is not supported
+    if info.center.0 < 0.0 || info.center.1 < 0.0 {
+        let (x, y) = info
+            .start_center
+            .unwrap_or((img_width / 2.0, img_height / 2.0));
+        info.center = (x.clamp(0.0, img_width), y.clamp(0.0, img_height));
+        info.magnify = match info.scale {
+            Scale::Fit => 1.0,
+            Scale::Fill => f64::max(fit_width / img_width, fit_height / img_height),
+            Scale::ActualSize => fit_width / win_width as f64,
+            Scale::Zoom(magnify) => magnify,
+        }
+        .max(1.0);
+    }
+
+    let (clip_width, clip_height) = (fit_width / info.magnify, fit_height / info.magnify);
+    info.clip_size = (clip_width, clip_height);
+
+    // keep the clip inside the image, so that cells map linearly to image pixels
+    let keep_inside = |center: f64, clip: f64, size: f64| {
+        if clip >= size {
+            size / 2.0
+        } else {
+            center.clamp(clip / 2.0, size - clip / 2.0)
+        }
+    };
+    info.center = (
+        keep_inside(info.center.0, clip_width, img_width),
+        keep_inside(info.center.1, clip_height, img_height),
+    );
+
+    // whole pixels only; rounding the corner rather than truncating keeps
+    // the last pixel reachable when the view is pushed against the far edge
+    let corner = |center: f64, clip: f64, size: f64| {
+        (center - clip / 2.0)
+            .round()
+            .clamp(0.0, (size - clip.floor()).max(0.0)) as u32
+    };
+    let (w, h) = (clip_width as u32, clip_height as u32);
+    let (l, t) = (
+        corner(info.center.0, clip_width, img_width),
+        corner(info.center.1, clip_height, img_height),
+    );
+    let region = img.clamp_region((l, t, w, h));
+    let img = img.resample(region, win_width, win_height, info.filter);
+    let (img_width, img_height) = img.dimensions();
+
+    // create frame
+    let bg_color_light = Rgba([153, 153, 153, 255]);
+    let bg_color_dark = Rgba([102, 102, 102, 255]);
+    let mut frame = RgbaImage::new(win_width, win_height);
+
+    let (anchor_x, anchor_y) = ((win_width - img_width) / 2, (win_height - img_height) / 2);
+    info.mapping = Some(Mapping {
+        region,
+        placement: (anchor_x, anchor_y, img_width, img_height),
+        cell_size,
+        frame_size: (win_width, win_height),
+    });
+    for y in 0..img_height {
+        for x in 0..img_width {
+            let (fx, fy) = (x + anchor_x, y + anchor_y);
+            let bg = match info.background {
+                Background::Color(color) => color,
+                Background::Checker if (fx / checker + fy / checker).is_multiple_of(2) => {
+                    bg_color_light
+                }
+                Background::Checker => bg_color_dark,
+            };
+            frame.put_pixel(fx, fy, blend(*img.get_pixel(x, y), bg));
+        }
+    }
+    frame
+}
+
+/// Draws the frame with one of the character cell renderers.
+fn draw_cells(
+    frame: &mut RgbaImage,
+    info: &DisplayInfo,
+    screen: &mut Screen,
+    cols: u16,
+    rows: u16,
+) {
+    match info.renderer.glyphs() {
+        _ if info.renderer == Renderer::Ascii => {
+            mod_ascii::draw(frame, screen, cols, rows, info.color_mode)
+        }
+        Some(glyphs) => {
+            quantize(frame, info.color_mode, info.dither);
+            mod_cells::draw(frame, screen, cols, rows, glyphs, info.color_mode);
+        }
+        None => {
+            quantize(frame, info.color_mode, info.dither);
+            draw_half_block(frame, screen, cols, rows, info.color_mode);
+        }
+    }
+}
+
+fn draw_half_block(
+    frame: &RgbaImage,
+    screen: &mut Screen,
+    cols: u16,
+    rows: u16,
+    color_mode: ColorMode,
+) {
+    for y in 0..rows {
+        for x in 0..cols {
+            let (x, y) = (x as u32, y as u32);
+            let true_y = y * 2;
+
+            let upper_pixel = *frame.get_pixel(x, true_y);
+            let lower_pixel = *frame.get_pixel(x, true_y + 1);
+
+            let cell = Cell {
+                ch: '\u{2580}',
+                fg: to_color(upper_pixel, color_mode),
+                bg: to_color(lower_pixel, color_mode),
+            };
+            screen.set(x as u16, y as u16, cell);
+        }
     }
 }
 
-fn blend(pixel: Rgba<u8>, bg: Rgba<u8>) -> Color {
-    if bg[3] == 0 {
-        Color::Rgb { r: 0, g: 0, b: 0 }
+fn draw_help(screen: &mut Screen, term_width: u16, term_height: u16, ascii: bool) {
+    let version = env!("CARGO_PKG_VERSION");
+
+    let title = format!("Image In Terminal - v{}", version);
+    // the box has to leave the status line free
+    let rows = (term_height as usize).saturating_sub(5).max(1);
+    let msgs = fit_help_lines(
+        help_lines(),
+        rows,
+        (term_width as usize).saturating_sub(4),
+        ascii,
+    );
+    let (mut w2, h) = (0, msgs.len() as u16 + 2);
+    for msg in &msgs {
+        w2 = w2.max(msg.chars().count() as u16);
+    }
+    let w1 = w2.max(title.len() as u16);
+
+    let (anchor_help_w, anchor_help_h) = (
+        term_width.saturating_sub(w1 + 4) / 2,
+        term_height.saturating_sub(h + 2) / 2,
+    );
+
+    let help_padding = (w1 - w2) / 2;
+
+    // border characters: (side, top, bottom)
+    let (side, top, bottom) = if ascii {
+        ("|", "-", "-")
     } else {
-        let alpha = pixel[3] as f64 / 255.0;
-        Color::Rgb {
-            r: (pixel[0] as f64 * alpha + bg[0] as f64 * (1.0 - alpha)) as u8,
-            g: (pixel[1] as f64 * alpha + bg[1] as f64 * (1.0 - alpha)) as u8,
-            b: (pixel[2] as f64 * alpha + bg[2] as f64 * (1.0 - alpha)) as u8,
+        ("\u{2588}", "\u{2580}", "\u{2584}")
+    };
+
+    // the box is painted with an explicit background so that
+    // graphics protocol images placed below text stay hidden behind it
+    let (fg, bg) = (Color::White, Color::Black);
+
+    // show border
+    for x in 0..(w1 + 4) {
+        let edge = x == 0 || x == w1 + 3;
+        screen.print(
+            anchor_help_w + x,
+            anchor_help_h,
+            if edge { side } else { top },
+            fg,
+            bg,
+        );
+        for y in 0..h {
+            let ch = if edge { side } else { " " };
+            screen.print(anchor_help_w + x, anchor_help_h + 1 + y, ch, fg, bg);
+        }
+        let ch = if edge { side } else { bottom };
+        screen.print(anchor_help_w + x, anchor_help_h + h + 1, ch, fg, bg);
+    }
+
+    // show title
+    screen.print(anchor_help_w + 2, anchor_help_h + 1, &title, fg, bg);
+
+    // show help message
+    for (i, msg) in msgs.iter().enumerate() {
+        let (x, y) = (
+            anchor_help_w + 2 + help_padding,
+            anchor_help_h + 3 + i as u16,
+        );
+        screen.print(x, y, msg, fg, bg);
+    }
+}
+
+/// Puts the help lines into two columns when there are more than `rows` of
+/// them, and cuts lines and columns that do not fit off with an ellipsis.
+fn fit_help_lines(mut msgs: Vec<String>, rows: usize, width: usize, ascii: bool) -> Vec<String> {
+    let ellipsis = if ascii { "..." } else { "\u{2026}" };
+    let column_width = msgs
+        .iter()
+        .map(|msg| msg.chars().count())
+        .max()
+        .unwrap_or(0);
+    if msgs.len() > rows && column_width * 2 + 3 <= width {
+        let (left, right) = msgs.split_at(msgs.len().div_ceil(2));
+        msgs = left
+            .iter()
+            .zip(right.iter().map(Some).chain([None]))
+            .map(|(left, right)| match right {
+                Some(right) => format!("{:<column_width$}   {}", left, right),
+                None => left.clone(),
+            })
+            .collect();
+    }
+    if msgs.len() > rows {
+        msgs.truncate(rows - 1);
+        msgs.push(ellipsis.to_string());
+    }
+    let width = width.max(ellipsis.len());
+    for msg in &mut msgs {
+        if msg.chars().count() > width {
+            let kept = msg.chars().take(width - ellipsis.chars().count());
+            *msg = kept.chain(ellipsis.chars()).collect();
         }
     }
+    msgs
+}
+
+/// The first key bound to `action`, as hints show it.
+fn first_key(action: Action) -> Option<String> {
+    config()
+        .keymap
+        .keys(action)
+        .first()
+        .map(|key| key.to_string())
+}
+
+/// One line per bound action, with the keys of the larger steps after slashes,
+/// e.g. `w + / W : zoom in`.
+fn help_lines() -> Vec<String> {
+    let keymap = &config().keymap;
+    let keys = |action: Action| {
+        let keys = keymap.keys(action).iter().map(|key| key.to_string());
+        keys.collect::<Vec<_>>().join(" ")
+    };
+
+    let mut entries = Vec::new();
+    for action in Action::ALL {
+        let keys = match action.step() {
+            // the other step sizes are listed with the small one
+            Some(Step::Large | Step::Edge) => continue,
+            Some(Step::Small) => [Step::Small, Step::Large, Step::Edge]
+                .into_iter()
+                .filter_map(|step| action.with_step(step).map(keys))
+                .filter(|keys| !keys.is_empty())
+                .collect::<Vec<_>>()
+                .join(" / "),
+            None => keys(action),
+        };
+        if !keys.is_empty() {
+            entries.push((keys, action.description()));
+        }
+    }
+
+    let width = entries
+        .iter()
+        .map(|(keys, _)| keys.len())
+        .max()
+        .unwrap_or(0);
+    entries
+        .iter()
+        .map(|(keys, description)| format!("{:>width$} : {}", keys, description))
+        .collect()
+}
+
+fn blend(pixel: Rgba<u8>, bg: Rgba<u8>) -> Rgba<u8> {
+    let alpha = pixel[3] as f64 / 255.0;
+    Rgba([
+        (pixel[0] as f64 * alpha + bg[0] as f64 * (1.0 - alpha)) as u8,
+        (pixel[1] as f64 * alpha + bg[1] as f64 * (1.0 - alpha)) as u8,
+        (pixel[2] as f64 * alpha + bg[2] as f64 * (1.0 - alpha)) as u8,
+        255,
+    ])
 }
@@ -0,0 +1,110 @@
+use std::io::Error;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{poll, read, Event};
+
+use crate::mod_loader::Decoder;
+
+// longest wait for input before the message channel is checked again
+const MESSAGE_TICK: Duration = Duration::from_millis(50);
+
+/// Things that happen at a given time instead of in response to input.
+/// There is at most one pending timer of each kind.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Timer {
+    /// Turns the loading spinner.
+    Spinner,
+    /// Shows the next frame of an animation.
+    Frame,
+    /// Redraws once the terminal has stopped changing size.
+    Resize,
+    /// Checks whether the image file changed.
+    Watch,
+    /// Hides the notice in the status line.
+    Notice,
+    /// Advances the slideshow.
+    Slide,
+}
+
+/// Sent by background tasks to wake up the event loop.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Message {
+    /// An image finished decoding, or was replaced by another one while waiting.
+    Loaded,
+}
+
+/// Why `EventLoop::wait` returned.
+pub enum Wake {
+    Input(Event),
+    Timer(Timer),
+    Message(Message),
+}
+
+/// Waits for terminal input, timers and messages from background tasks
+/// without blocking on any one of them.
+pub struct EventLoop {
+    timers: Vec<(Instant, Timer)>,
+    receiver: Receiver<Message>,
+    decoder: Decoder,
+}
+
+impl Default for EventLoop {
+    fn default() -> EventLoop {
+        let (sender, receiver) = channel();
+        EventLoop {
+            timers: Vec::new(),
+            receiver,
+            decoder: Decoder::new(sender),
+        }
+    }
+}
+
+impl EventLoop {
+    /// Decoder for images; every finished image wakes up `wait`.
+    pub fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    /// Fires `timer` after `delay`, replacing a pending timer of the same kind.
+    pub fn schedule(&mut self, timer: Timer, delay: Duration) {
+        self.cancel(timer);
+        self.timers.push((Instant::now() + delay, timer));
+    }
+
+    pub fn cancel(&mut self, timer: Timer) {
+        self.timers.retain(|(_, pending)| *pending != timer);
+    }
+
+    pub fn is_scheduled(&self, timer: Timer) -> bool {
+        self.timers.iter().any(|(_, pending)| *pending == timer)
+    }
+
+    /// Waits for the next input event, due timer or message, whichever comes first.
+    pub fn wait(&mut self) -> Result<Wake, Error> {
+        loop {
+            if let Ok(message) = self.receiver.try_recv() {
+                return Ok(Wake::Message(message));
+            }
+
+            let now = Instant::now();
+            let next = self
+                .timers
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (due, _))| *due)
+                .map(|(i, (due, _))| (i, *due));
+            let timeout = match next {
+                Some((i, due)) if due <= now => {
+                    return Ok(Wake::Timer(self.timers.remove(i).1));
+                }
+                Some((_, due)) => (due - now).min(MESSAGE_TICK),
+                None => MESSAGE_TICK,
+            };
+
+            if poll(timeout)? {
+                return Ok(Wake::Input(read()?));
+            }
+        }
+    }
+}
@@ -1,52 +1,149 @@
 use std::cmp::{max, min};
+use std::fs;
 use std::io::{stdout, Error, Write};
+use std::time::{Duration, Instant, SystemTime};
 
 use crossterm::cursor::MoveToColumn;
 
 use crossterm::event::KeyCode::{Backspace, Char, Delete, End, Enter, Esc, Home, Left, Right};
-use crossterm::event::{read, Event, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::event::{
+    read, Event, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 use crossterm::style::Print;
 use crossterm::terminal::{Clear, ClearType};
 use crossterm::{execute, queue};
 
-use image::{open, DynamicImage, ImageError};
+use crate::mod_config::config;
+use crate::mod_display::{format_interval, DisplayInfo};
+use crate::mod_event_loop::{EventLoop, Message, Timer, Wake};
+use crate::mod_keymap::{Action, Step};
+use crate::mod_loader::ImageState;
+use crate::mod_playlist::{Playlist, View};
+
+// how often the loading spinner is redrawn
+const LOADING_TICK: Duration = Duration::from_millis(100);
+
+// how long the terminal size has to stay the same before redrawing
+const RESIZE_DELAY: Duration = Duration::from_millis(50);
 
-use crate::mod_display::DisplayInfo;
+// how often a watched file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
 
-struct Config {
-    magnify_step: f64,
-    magnify_step_large: f64,
-    move_step_ratio: f64,
-    move_step_ratio_large: f64,
+// how long a notice stays in the status line
+const NOTICE_DURATION: Duration = Duration::from_secs(3);
+
+// longest pause between the clicks of a double-click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+#[derive(Default)]
+struct Mouse {
+    /// Cell where the left button went down and the center at that time.
+    drag_start: Option<((u16, u16), (f64, f64))>,
+    /// Time and cell of the last click, for detecting double-clicks.
+    last_click: Option<(Instant, (u16, u16))>,
 }
 
-static CONFIG: Config = Config {
-    magnify_step: 0.1,
-    magnify_step_large: 1.0,
-    move_step_ratio: 0.05,
-    move_step_ratio_large: 0.2,
-};
+#[derive(Default)]
+struct Watch {
+    /// Watched file with its modification time and size at the last check.
+    stamp: Option<(String, Option<SystemTime>, u64)>,
+    /// Reload decoding in the background, together with its path;
+    /// the old image stays on screen until it is done.
+    reload: Option<(String, ImageState)>,
+}
+
+/// State of the viewer that is not part of what is displayed.
+#[derive(Default)]
+pub struct ViewerState {
+    mouse: Mouse,
+    watch: Watch,
+}
 
+/// Waits for the next event and applies it. Returns `false` to exit.
 pub fn handle_events(
-    image: &mut Result<DynamicImage, ImageError>,
+    events: &mut EventLoop,
+    playlist: &mut Playlist,
+    image: &mut ImageState,
     info: &mut DisplayInfo,
+    state: &mut ViewerState,
 ) -> Result<bool, Error> {
-    match read() {
-        Ok(Event::Key(key_event)) if key_event.kind == KeyEventKind::Press => {
-            handle_key_events(key_event, image, info)
+    match events.wait()? {
+        Wake::Input(Event::Key(key_event)) if key_event.kind == KeyEventKind::Press => {
+            handle_key_events(key_event, events, playlist, image, info)
         }
-        Ok(Event::Resize(_, _)) => {
+        Wake::Input(Event::Mouse(mouse_event)) => {
+            handle_mouse_events(mouse_event, image, info, &mut state.mouse);
+            Ok(true)
+        }
+        Wake::Input(Event::Resize(_, _)) => {
+            // a window being dragged to a new size sends a burst of these
+            events.schedule(Timer::Resize, RESIZE_DELAY);
+            Ok(true)
+        }
+        Wake::Timer(Timer::Resize) => {
             info.clip_size = (-1.0, -1.0);
             Ok(true)
         }
-        Err(e) => Err(e),
-        _ => Ok(true),
+        Wake::Timer(Timer::Watch) => {
+            check_file(events, image, info, &mut state.watch);
+            Ok(true)
+        }
+        Wake::Timer(Timer::Slide) => {
+            advance_slideshow(events, playlist, image, info);
+            Ok(true)
+        }
+        Wake::Timer(Timer::Notice) => {
+            info.notice = None;
+            Ok(true)
+        }
+        Wake::Message(Message::Loaded) => {
+            finish_reload(events, image, info, &mut state.watch);
+            Ok(true)
+        }
+        // the caller picks up finished images and due frames
+        Wake::Timer(Timer::Spinner | Timer::Frame) => Ok(true),
+        Wake::Input(_) => Ok(true),
+    }
+}
+
+/// Keeps the timers in line with the image: the spinner turns while it
+/// is loading, an animation wakes the loop up for its next frame and
+/// a watched file is checked regularly.
+pub fn schedule_timers(events: &mut EventLoop, image: &ImageState, info: &DisplayInfo) {
+    if !info.watch {
+        events.cancel(Timer::Watch);
+    } else if !events.is_scheduled(Timer::Watch) {
+        events.schedule(Timer::Watch, WATCH_INTERVAL);
+    }
+
+    // every image is shown for the whole interval once it is loaded
+    let slideshow = info.slideshow;
+    if !slideshow.active || slideshow.paused || image.is_loading() {
+        events.cancel(Timer::Slide);
+    } else if !events.is_scheduled(Timer::Slide) {
+        events.schedule(Timer::Slide, slideshow.interval);
+    }
+
+    if !image.is_loading() {
+        events.cancel(Timer::Spinner);
+    } else if !events.is_scheduled(Timer::Spinner) {
+        events.schedule(Timer::Spinner, LOADING_TICK);
+    }
+
+    match image {
+        ImageState::Ready(animation) => match animation.time_to_next_frame() {
+            Some(delay) => events.schedule(Timer::Frame, delay),
+            None => events.cancel(Timer::Frame),
+        },
+        _ => events.cancel(Timer::Frame),
     }
 }
 
 fn handle_key_events(
     key_event: KeyEvent,
-    image: &mut Result<DynamicImage, ImageError>,
+    events: &mut EventLoop,
+    playlist: &mut Playlist,
+    image: &mut ImageState,
     info: &mut DisplayInfo,
 ) -> Result<bool, Error> {
     // println!("{} + {}", key_event.modifiers, key_event.code);
@@ -54,158 +151,482 @@ fn handle_key_events(
         return Ok(true);
     };
 
-    match key_event.modifiers {
-        // simple key
-        KeyModifiers::NONE => match key_event.code {
-            Char('q') => Ok(false),
-            Char('o') => {
-                let file_path = input_box("input file path: ")?;
-                execute!(
-                    stdout(),
-                    Clear(ClearType::CurrentLine),
-                    Print(format!("file_path: {}", file_path)),
-                )
-                .unwrap();
-                *image = open(file_path.clone());
-                info.image_file_path = file_path.clone();
-                info.magnify = 1.0;
-                info.center = (-1.0, -1.0);
-                Ok(true)
-            }
-            Char('w') => {
-                info.magnify += CONFIG.magnify_step;
-                Ok(true)
-            }
-            Char('s') => {
-                if info.magnify >= 1.0 + CONFIG.magnify_step {
-                    info.magnify -= CONFIG.magnify_step;
-                }
+    // Ctrl-C always quits, whatever the keymap says
+    if key_event.modifiers == KeyModifiers::CONTROL && key_event.code == Char('c') {
+        return Ok(false);
+    }
+    let Some(action) = config().keymap.resolve(key_event) else {
+        return Ok(true);
+    };
+
+    // only allow to abort or skip the image while loading
+    if image.is_loading() {
+        return match action {
+            Action::Quit => Ok(false),
+            Action::Cancel => {
+                image.cancel();
                 Ok(true)
             }
-            Char('h') => {
-                if info.clip_size.0 > 0.0
-                    && info.center.0
-                        - CONFIG.move_step_ratio * info.clip_size.0
-                        - info.clip_size.0 / 2.0
-                        > 0.0
-                {
-                    info.center.0 -= CONFIG.move_step_ratio * info.clip_size.0;
-                }
+            Action::NextImage => {
+                switch_image(events, playlist, image, info, Playlist::next);
                 Ok(true)
             }
-            Char('l') => {
-                if info.clip_size.0 > 0.0
-                    && info.center.0
-                        + CONFIG.move_step_ratio * info.clip_size.0
-                        + info.clip_size.0 / 2.0
-                        < info.img_size.0 as f64
-                {
-                    info.center.0 += CONFIG.move_step_ratio * info.clip_size.0;
-                }
+            Action::PreviousImage => {
+                switch_image(events, playlist, image, info, Playlist::previous);
                 Ok(true)
             }
-            Char('k') => {
-                if info.clip_size.1 > 0.0
-                    && info.center.1
-                        - CONFIG.move_step_ratio * info.clip_size.1
-                        - info.clip_size.1 / 2.0
-                        > 0.0
-                {
-                    info.center.1 -= CONFIG.move_step_ratio * info.clip_size.1;
-                }
-                Ok(true)
+            _ => Ok(true),
+        };
+    }
+
+    match action {
+        Action::Quit => Ok(false),
+        Action::Cancel => Ok(true),
+        Action::Open => {
+            let file_path = input_box("input file path: ")?;
+            execute!(
+                stdout(),
+                Clear(ClearType::CurrentLine),
+                Print(format!("file_path: {}", file_path)),
+            )
+            .unwrap();
+            *playlist = Playlist::new(&[file_path])?;
+            load_current(events, playlist, image, info);
+            Ok(true)
+        }
+        Action::NextImage => {
+            switch_image(events, playlist, image, info, Playlist::next);
+            Ok(true)
+        }
+        Action::PreviousImage => {
+            switch_image(events, playlist, image, info, Playlist::previous);
+            Ok(true)
+        }
+        Action::ZoomIn(step) => {
+            info.magnify = zoom_in(info, step);
+            Ok(true)
+        }
+        Action::ZoomOut(step) => {
+            // zooming out stops where the whole image is visible
+            info.magnify = match step {
+                Step::Small => info.magnify - config().magnify_step,
+                Step::Large => info.magnify - config().magnify_step_large,
+                Step::Edge => 1.0,
             }
-            Char('j') => {
-                if info.clip_size.1 > 0.0
-                    && info.center.1
-                        + CONFIG.move_step_ratio * info.clip_size.1
-                        + info.clip_size.1 / 2.0
-                        < info.img_size.1 as f64
-                {
-                    info.center.1 += CONFIG.move_step_ratio * info.clip_size.1;
+            .max(1.0);
+            Ok(true)
+        }
+        Action::PanLeft(step) => {
+            navigate(info, (-1.0, 0.0), step);
+            Ok(true)
+        }
+        Action::PanRight(step) => {
+            navigate(info, (1.0, 0.0), step);
+            Ok(true)
+        }
+        Action::PanUp(step) => {
+            navigate(info, (0.0, -1.0), step);
+            Ok(true)
+        }
+        Action::PanDown(step) => {
+            navigate(info, (0.0, 1.0), step);
+            Ok(true)
+        }
+        Action::Reset => {
+            reset_view(info);
+            Ok(true)
+        }
+        Action::ToggleHelp => {
+            info.show_help = !info.show_help;
+            Ok(true)
+        }
+        Action::NextRenderer => {
+            info.renderer = info.renderer.next();
+            Ok(true)
+        }
+        Action::NextColors => {
+            info.color_mode = info.color_mode.next();
+            Ok(true)
+        }
+        Action::NextFilter => {
+            info.filter = info.filter.next();
+            Ok(true)
+        }
+        Action::TogglePause
+        | Action::NextFrame
+        | Action::PreviousFrame
+        | Action::PlayFaster
+        | Action::PlaySlower => {
+            if action == Action::TogglePause && info.slideshow.active {
+                info.slideshow.paused = !info.slideshow.paused;
+            } else if let ImageState::Ready(animation) = image {
+                match action {
+                    Action::TogglePause => animation.toggle_pause(),
+                    Action::NextFrame => animation.step(true),
+                    Action::PreviousFrame => animation.step(false),
+                    Action::PlayFaster => animation.faster(),
+                    _ => animation.slower(),
                 }
-                Ok(true)
             }
-            Char('r') => {
-                info.center = (-1.0, -1.0);
-                info.magnify = 1.0;
-                Ok(true)
+            Ok(true)
+        }
+        Action::ToggleSlideshow => {
+            let slideshow = &mut info.slideshow;
+            slideshow.active = !slideshow.active;
+            slideshow.paused = false;
+            let notice = if slideshow.active {
+                format!("slideshow every {}", format_interval(slideshow.interval))
+            } else {
+                "slideshow stopped".to_string()
+            };
+            show_notice(events, info, &notice);
+            Ok(true)
+        }
+        Action::ToggleWatch => {
+            if info.image_file_path == "-" {
+                show_notice(events, info, "standard input cannot be watched");
+            } else {
+                info.watch = !info.watch;
+                let notice = if info.watch {
+                    "watching for changes"
+                } else {
+                    "stopped watching"
+                };
+                show_notice(events, info, notice);
             }
-            Char('y') => {
-                info.show_help = !info.show_help;
-                Ok(true)
+            Ok(true)
+        }
+        Action::ToggleInspect => {
+            // start at the pixel in the middle of the view
+            info.inspect = match info.inspect {
+                Some(_) => None,
+                None => Some((info.center.0.max(0.0) as u32, info.center.1.max(0.0) as u32)),
+            };
+            Ok(true)
+        }
+    }
+}
+
+/// Magnification after zooming in by `step`. `Edge` goes to the actual size,
+/// one image pixel per terminal pixel (or sub-pixel of text renderers),
+/// unless the view is already closer.
+fn zoom_in(info: &DisplayInfo, step: Step) -> f64 {
+    match step {
+        Step::Small => info.magnify + config().magnify_step,
+        Step::Large => info.magnify + config().magnify_step_large,
+        // the same magnification as `Scale::ActualSize`
+        Step::Edge => match info.mapping {
+            Some(mapping) => {
+                let fit_width = info.clip_size.0 * info.magnify;
+                info.magnify.max(fit_width / mapping.frame_size.0 as f64)
             }
-            _ => Ok(true),
-        },
-        // ctrl pressed
-        KeyModifiers::CONTROL => match key_event.code {
-            Char('c') => Ok(false),
-            _ => Ok(true),
+            None => info.magnify,
         },
-        KeyModifiers::SHIFT => match key_event.code {
-            Char('+') => {
-                info.magnify += CONFIG.magnify_step;
-                Ok(true)
-            }
-            Char('W') => {
-                info.magnify += CONFIG.magnify_step_large;
-                Ok(true)
+    }
+}
+
+/// Pans the view, or moves the cursor in inspect mode.
+fn navigate(info: &mut DisplayInfo, direction: (f64, f64), step: Step) {
+    if info.inspect.is_some() {
+        move_cursor(info, direction, step);
+    } else {
+        pan(info, direction, step);
+    }
+}
+
+/// Moves the inspect cursor one cell, or at least one pixel, in `direction`.
+/// The view follows when the cursor leaves it.
+fn move_cursor(info: &mut DisplayInfo, direction: (f64, f64), step: Step) {
+    let (Some(cursor), Some(mapping)) = (info.inspect, info.mapping) else {
+        return;
+    };
+    // image pixels per cell
+    let scale = mapping.scale();
+    let cell = (
+        mapping.cell_size.0 as f64 / scale.0,
+        mapping.cell_size.1 as f64 / scale.1,
+    );
+    let (l, t, w, h) = mapping.region;
+
+    let axes = [
+        (
+            cursor.0,
+            &mut info.center.0,
+            cell.0,
+            (l, w),
+            info.clip_size.0,
+            info.img_size.0,
+            direction.0,
+        ),
+        (
+            cursor.1,
+            &mut info.center.1,
+            cell.1,
+            (t, h),
+            info.clip_size.1,
+            info.img_size.1,
+            direction.1,
+        ),
+    ];
+    let moved = axes.map(
+        |(pos, center, cell, (first, count), clip, size, direction)| {
+            if size == 0 || direction == 0.0 {
+                return pos;
             }
-            Char('S') => {
-                if info.magnify - CONFIG.magnify_step_large > 1.0 {
-                    info.magnify -= CONFIG.magnify_step_large;
-                }
-                Ok(true)
+            let distance = match step {
+                Step::Small => cell.round().max(1.0),
+                Step::Large => (config().move_step_ratio_large * clip).round().max(1.0),
+                Step::Edge => size as f64,
+            };
+            let pos = (pos as f64 + direction * distance).clamp(0.0, size as f64 - 1.0) as u32;
+            // scroll the drawn region by whole pixels, just far enough to show the pixel
+            if pos < first {
+                *center -= (first - pos) as f64;
+            } else if pos >= first + count {
+                *center += (pos + 1 - first - count) as f64;
             }
-            Char('H') => {
-                if info.clip_size.0 > 0.0
-                    && info.center.0
-                        - CONFIG.move_step_ratio_large * info.clip_size.0
-                        - info.clip_size.0 / 2.0
-                        > 0.0
-                {
-                    info.center.0 -= CONFIG.move_step_ratio_large * info.clip_size.0;
-                }
-                Ok(true)
+            pos
+        },
+    );
+    info.inspect = Some((moved[0], moved[1]));
+}
+
+/// Moves the center one step in `direction`, whose components are -1, 0 or 1,
+/// as long as the view does not leave the image on that side.
+fn pan(info: &mut DisplayInfo, direction: (f64, f64), step: Step) {
+    let axes = [
+        (
+            &mut info.center.0,
+            info.clip_size.0,
+            info.img_size.0 as f64,
+            direction.0,
+        ),
+        (
+            &mut info.center.1,
+            info.clip_size.1,
+            info.img_size.1 as f64,
+            direction.1,
+        ),
+    ];
+    for (center, clip, size, direction) in axes {
+        if clip <= 0.0 || direction == 0.0 {
+            continue;
+        }
+        let ratio = match step {
+            Step::Small => config().move_step_ratio,
+            Step::Large => config().move_step_ratio_large,
+            // the whole axis is already visible when the clip is wider than the image
+            Step::Edge if clip < size => {
+                *center = if direction < 0.0 {
+                    clip / 2.0
+                } else {
+                    size - clip / 2.0
+                };
+                continue;
             }
-            Char('L') => {
-                if info.clip_size.0 > 0.0
-                    && info.center.0
-                        + CONFIG.move_step_ratio_large * info.clip_size.0
-                        + info.clip_size.0 / 2.0
-                        < info.img_size.0 as f64
-                {
-                    info.center.0 += CONFIG.move_step_ratio_large * info.clip_size.0;
+            Step::Edge => continue,
+        };
+        let moved = *center + direction * ratio * clip;
+        let inside = if direction < 0.0 {
+            moved - clip / 2.0 > 0.0
+        } else {
+            moved + clip / 2.0 < size
+        };
+        if inside {
+            *center = moved;
+        }
+    }
+}
+
+fn handle_mouse_events(
+    mouse_event: MouseEvent,
+    image: &ImageState,
+    info: &mut DisplayInfo,
+    mouse: &mut Mouse,
+) {
+    // the view is only known once an image has been drawn
+    let (ImageState::Ready(_), Some(mapping)) = (image, info.mapping) else {
+        return;
+    };
+    let cell = (mouse_event.column, mouse_event.row);
+
+    match mouse_event.kind {
+        MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+            let magnify = if mouse_event.kind == MouseEventKind::ScrollUp {
+                info.magnify + config().magnify_step
+            } else {
+                (info.magnify - config().magnify_step).max(1.0)
+            };
+            let zoom = magnify / info.magnify;
+            // keep the pixel under the pointer in place: the drawn region
+            // is scaled by `zoom`, so its corner follows from the pointer
+            let pointer = (
+                (cell.0 as f64 + 0.5) * mapping.cell_size.0 as f64,
+                (cell.1 as f64 + 0.5) * mapping.cell_size.1 as f64,
+            );
+            let anchor = mapping.frame_to_image(pointer);
+            let scale = mapping.scale();
+            let corner = (
+                anchor.0 - (pointer.0 - mapping.placement.0 as f64) / (scale.0 * zoom),
+                anchor.1 - (pointer.1 - mapping.placement.1 as f64) / (scale.1 * zoom),
+            );
+            info.center = (
+                corner.0 + info.clip_size.0 / zoom / 2.0,
+                corner.1 + info.clip_size.1 / zoom / 2.0,
+            );
+            info.magnify = magnify;
+        }
+        MouseEventKind::Down(MouseButton::Left) => {
+            let double_click = mouse
+                .last_click
+                .is_some_and(|(time, last)| last == cell && time.elapsed() < DOUBLE_CLICK);
+            if double_click {
+                reset_view(info);
+                mouse.last_click = None;
+                mouse.drag_start = None;
+            } else {
+                if info.inspect.is_some() {
+                    let (x, y) = mapping.cell_to_image(cell.0, cell.1);
+                    info.inspect = Some((x.max(0.0) as u32, y.max(0.0) as u32));
                 }
-                Ok(true)
+                mouse.last_click = Some((Instant::now(), cell));
+                mouse.drag_start = Some((cell, info.center));
             }
-            Char('K') => {
-                if info.clip_size.1 > 0.0
-                    && info.center.1
-                        - CONFIG.move_step_ratio_large * info.clip_size.1
-                        - info.clip_size.1 / 2.0
-                        > 0.0
-                {
-                    info.center.1 -= CONFIG.move_step_ratio_large * info.clip_size.1;
-                }
-                Ok(true)
+        }
+        MouseEventKind::Drag(MouseButton::Left) => {
+            if let Some((start, center)) = mouse.drag_start {
+                // the image follows the pointer
+                let from = mapping.cell_to_image(start.0, start.1);
+                let to = mapping.cell_to_image(cell.0, cell.1);
+                info.center = (center.0 - (to.0 - from.0), center.1 - (to.1 - from.1));
             }
-            Char('J') => {
-                if info.clip_size.1 > 0.0
-                    && info.center.1
-                        + CONFIG.move_step_ratio_large * info.clip_size.1
-                        + info.clip_size.1 / 2.0
-                        < info.img_size.1 as f64
-                {
-                    info.center.1 += CONFIG.move_step_ratio_large * info.clip_size.1;
-                }
-                Ok(true)
+        }
+        MouseEventKind::Up(MouseButton::Left) => mouse.drag_start = None,
+        _ => {}
+    }
+}
+
+/// Starts reloading the shown image when its file changed since the last check.
+fn check_file(events: &EventLoop, image: &ImageState, info: &DisplayInfo, watch: &mut Watch) {
+    let path = &info.image_file_path;
+    if image.is_loading() || path == "-" {
+        return;
+    }
+    if watch.reload.is_some() {
+        return;
+    }
+    // the file may be missing for a moment while it is replaced
+    let Ok(metadata) = fs::metadata(path) else {
+        return;
+    };
+    let stamp = (path.clone(), metadata.modified().ok(), metadata.len());
+    let previous = watch.stamp.replace(stamp.clone());
+    // a stamp of another file means the image was switched, not changed
+    if previous.is_some_and(|previous| previous.0 == stamp.0 && previous != stamp) {
+        watch.reload = Some((path.clone(), ImageState::load(path, events.decoder())));
+    }
+}
+
+/// Shows a finished reload. A failed one, e.g. of a file that is still
+/// being written, leaves the old image in place.
+fn finish_reload(
+    events: &mut EventLoop,
+    image: &mut ImageState,
+    info: &mut DisplayInfo,
+    watch: &mut Watch,
+) {
+    let Some((path, mut reload)) = watch.reload.take() else {
+        return;
+    };
+    if !reload.update() {
+        watch.reload = Some((path, reload));
+        return;
+    }
+    if path != info.image_file_path {
+        return;
+    }
+    match reload {
+        ImageState::Ready(animation) => {
+            // zoom and center only carry over to an image of the same size
+            if animation.image().dimensions() != info.img_size {
+                reset_view(info);
             }
+            *image = ImageState::Ready(animation);
+            show_notice(events, info, "reloaded");
+        }
+        ImageState::Failed(e) => show_notice(events, info, &format!("reload failed: {}", e)),
+        ImageState::Loading(_) => {}
+    }
+}
 
-            _ => Ok(true),
-        },
-        _ => Ok(true),
+/// Moves the slideshow to the next image, shown fitted to the window.
+/// Without looping it stops at the last image.
+fn advance_slideshow(
+    events: &mut EventLoop,
+    playlist: &mut Playlist,
+    image: &mut ImageState,
+    info: &mut DisplayInfo,
+) {
+    if playlist.is_last() && !info.slideshow.looping {
+        info.slideshow.active = false;
+        show_notice(events, info, "slideshow finished");
+        return;
     }
+    switch_image(events, playlist, image, info, Playlist::next);
+    reset_view(info);
+}
+
+/// Fits the image to the window again.
+fn reset_view(info: &mut DisplayInfo) {
+    info.center = (-1.0, -1.0);
+    info.magnify = 1.0;
+}
+
+fn show_notice(events: &mut EventLoop, info: &mut DisplayInfo, notice: &str) {
+    info.notice = Some(notice.to_string());
+    events.schedule(Timer::Notice, NOTICE_DURATION);
+}
+
+/// Remembers the view of the current image and moves to another one with `step`.
+fn switch_image(
+    events: &mut EventLoop,
+    playlist: &mut Playlist,
+    image: &mut ImageState,
+    info: &mut DisplayInfo,
+    step: fn(&mut Playlist),
+) {
+    if let ImageState::Ready(_) = image {
+        playlist.save_view(View {
+            magnify: info.magnify,
+            center: info.center,
+        });
+    }
+    step(playlist);
+    load_current(events, playlist, image, info);
+}
+
+/// Starts loading the current image of the playlist,
+/// restoring its view if it was shown before.
+pub fn load_current(
+    events: &mut EventLoop,
+    playlist: &Playlist,
+    image: &mut ImageState,
+    info: &mut DisplayInfo,
+) {
+    let view = playlist.view().unwrap_or(View {
+        magnify: 1.0,
+        center: (-1.0, -1.0),
+    });
+    *image = ImageState::load(playlist.current(), events.decoder());
+    // the slideshow interval starts over with every image
+    events.cancel(Timer::Slide);
+    info.image_file_path = playlist.current().to_string();
+    info.position = playlist.position();
+    info.magnify = view.magnify;
+    info.center = view.center;
+    info.clip_size = (-1.0, -1.0);
+    info.img_size = (0, 0);
+    info.mapping = None;
 }
 
 fn input_box(input_msg: &str) -> Result<String, Error> {
@@ -231,19 +652,14 @@ fn input_box(input_msg: &str) -> Result<String, Error> {
                         input.insert(pos, c);
                         pos += 1;
                     }
-                    Backspace => {
-                        if pos > 0 {
-                            input.remove(pos - 1);
-                            pos -= 1;
-                        }
+                    Backspace if pos > 0 => {
+                        input.remove(pos - 1);
+                        pos -= 1;
                     }
-                    Delete => {
-                        if pos < input.len() {
-                            input.remove(pos);
-                        }
+                    Delete if pos < input.len() => {
+                        input.remove(pos);
                     }
                     Enter => {
-                        println!();
                         return Ok(input);
                     }
 
@@ -273,3 +689,74 @@ fn input_box(input_msg: &str) -> Result<String, Error> {
         }
     }
 }
+
+/// Points stdout at the controlling terminal when it is redirected,
+/// so that the viewer can draw even when its output is piped.
+/// Keyboard input is read from the terminal by crossterm in any case.
+#[cfg(unix)]
+pub fn attach_tty() -> Result<(), Error> {
+    use std::fs::OpenOptions;
+    use std::io::IsTerminal;
+    use std::os::unix::io::AsRawFd;
+
+    if stdout().is_terminal() {
+        return Ok(());
+    }
+    let tty = OpenOptions::new().write(true).open("/dev/tty")?;
+    // SAFETY: both descriptors are open; dup2 leaves `tty` owning its own descriptor
+    if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn attach_tty() -> Result<(), Error> {
+    Ok(())
+}
+
+/// Reads raw bytes sent by the terminal until `done` accepts them or `timeout` expires.
+/// Used for replies to terminal queries, which crossterm does not report as events.
+#[cfg(unix)]
+pub fn read_reply(timeout: Duration, done: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::unix::io::AsRawFd;
+    use std::time::Instant;
+
+    let mut reply = Vec::new();
+    let Ok(mut tty) = File::open("/dev/tty") else {
+        return reply;
+    };
+
+    let deadline = Instant::now() + timeout;
+    while !done(&reply) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+
+        let mut fds = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `fds` is a single valid pollfd that outlives the call
+        let ready = unsafe { libc::poll(&mut fds, 1, remaining.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            break;
+        }
+
+        let mut buf = [0; 256];
+        match tty.read(&mut buf) {
+            Ok(n) if n > 0 => reply.extend_from_slice(&buf[..n]),
+            _ => break,
+        }
+    }
+    reply
+}
+
+#[cfg(not(unix))]
+pub fn read_reply(_timeout: Duration, _done: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+    Vec::new()
+}
@@ -0,0 +1,35 @@
+use std::io::stdout;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use crossterm::cursor::MoveTo;
+use crossterm::queue;
+use crossterm::style::Print;
+use image::RgbaImage;
+
+use crate::mod_display::encode_png;
+
+/// Sends the frame as an inline PNG file and stretches it over `cols` x `rows`
+/// cells starting at the top-left corner.
+pub fn draw(frame: &RgbaImage, cols: u16, rows: u16) {
+    queue!(
+        stdout(),
+        MoveTo(0, 0),
+        Print(sequence(frame, cols, rows, false))
+    )
+    .unwrap();
+}
+
+/// Escape sequence showing the frame over `cols` x `rows` cells at the cursor,
+/// which is moved past the image when `move_cursor` is set.
+pub fn sequence(frame: &RgbaImage, cols: u16, rows: u16, move_cursor: bool) -> String {
+    let png = encode_png(frame);
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=0;doNotMoveCursor={}:{}\x07",
+        png.len(),
+        cols,
+        rows,
+        if move_cursor { 0 } else { 1 },
+        STANDARD.encode(&png)
+    )
+}
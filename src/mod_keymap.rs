@@ -0,0 +1,534 @@
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Size of a zoom or pan step, see the `*_large` settings of the config.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Step {
+    Small,
+    Large,
+    /// All the way to the image border; zooming goes out to the whole
+    /// image or in to the actual size.
+    Edge,
+}
+
+/// Everything a key can be bound to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Action {
+    Quit,
+    Cancel,
+    Open,
+    ZoomIn(Step),
+    ZoomOut(Step),
+    PanLeft(Step),
+    PanRight(Step),
+    PanUp(Step),
+    PanDown(Step),
+    Reset,
+    ToggleHelp,
+    NextRenderer,
+    NextColors,
+    NextFilter,
+    NextImage,
+    PreviousImage,
+    ToggleInspect,
+    TogglePause,
+    NextFrame,
+    PreviousFrame,
+    PlayFaster,
+    PlaySlower,
+    ToggleWatch,
+    ToggleSlideshow,
+}
+
+impl Action {
+    /// All actions, in the order they are listed in the help.
+    pub const ALL: [Action; 36] = [
+        Action::ZoomIn(Step::Small),
+        Action::ZoomIn(Step::Large),
+        Action::ZoomIn(Step::Edge),
+        Action::ZoomOut(Step::Small),
+        Action::ZoomOut(Step::Large),
+        Action::ZoomOut(Step::Edge),
+        Action::PanLeft(Step::Small),
+        Action::PanLeft(Step::Large),
+        Action::PanLeft(Step::Edge),
+        Action::PanRight(Step::Small),
+        Action::PanRight(Step::Large),
+        Action::PanRight(Step::Edge),
+        Action::PanDown(Step::Small),
+        Action::PanDown(Step::Large),
+        Action::PanDown(Step::Edge),
+        Action::PanUp(Step::Small),
+        Action::PanUp(Step::Large),
+        Action::PanUp(Step::Edge),
+        Action::Reset,
+        Action::NextRenderer,
+        Action::NextColors,
+        Action::NextFilter,
+        Action::NextImage,
+        Action::PreviousImage,
+        Action::ToggleInspect,
+        Action::TogglePause,
+        Action::NextFrame,
+        Action::PreviousFrame,
+        Action::PlayFaster,
+        Action::PlaySlower,
+        Action::ToggleWatch,
+        Action::ToggleSlideshow,
+        Action::ToggleHelp,
+        Action::Open,
+        Action::Cancel,
+        Action::Quit,
+    ];
+
+    /// Name used in the `[keys]` table of the config file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Cancel => "cancel",
+            Action::Open => "open",
+            Action::ZoomIn(Step::Small) => "zoom_in",
+            Action::ZoomIn(Step::Large) => "zoom_in_large",
+            Action::ZoomIn(Step::Edge) => "zoom_in_edge",
+            Action::ZoomOut(Step::Small) => "zoom_out",
+            Action::ZoomOut(Step::Large) => "zoom_out_large",
+            Action::ZoomOut(Step::Edge) => "zoom_out_edge",
+            Action::PanLeft(Step::Small) => "pan_left",
+            Action::PanLeft(Step::Large) => "pan_left_large",
+            Action::PanLeft(Step::Edge) => "pan_left_edge",
+            Action::PanRight(Step::Small) => "pan_right",
+            Action::PanRight(Step::Large) => "pan_right_large",
+            Action::PanRight(Step::Edge) => "pan_right_edge",
+            Action::PanUp(Step::Small) => "pan_up",
+            Action::PanUp(Step::Large) => "pan_up_large",
+            Action::PanUp(Step::Edge) => "pan_up_edge",
+            Action::PanDown(Step::Small) => "pan_down",
+            Action::PanDown(Step::Large) => "pan_down_large",
+            Action::PanDown(Step::Edge) => "pan_down_edge",
+            Action::Reset => "reset",
+            Action::ToggleHelp => "toggle_help",
+            Action::NextRenderer => "next_renderer",
+            Action::NextColors => "next_colors",
+            Action::NextFilter => "next_filter",
+            Action::NextImage => "next_image",
+            Action::PreviousImage => "previous_image",
+            Action::ToggleInspect => "toggle_inspect",
+            Action::TogglePause => "toggle_pause",
+            Action::NextFrame => "next_frame",
+            Action::PreviousFrame => "previous_frame",
+            Action::PlayFaster => "play_faster",
+            Action::PlaySlower => "play_slower",
+            Action::ToggleWatch => "toggle_watch",
+            Action::ToggleSlideshow => "toggle_slideshow",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    pub fn step(&self) -> Option<Step> {
+        match self {
+            Action::ZoomIn(step)
+            | Action::ZoomOut(step)
+            | Action::PanLeft(step)
+            | Action::PanRight(step)
+            | Action::PanUp(step)
+            | Action::PanDown(step) => Some(*step),
+            _ => None,
+        }
+    }
+
+    /// The same action with another step size, `None` for actions without steps.
+    pub fn with_step(&self, step: Step) -> Option<Action> {
+        match self {
+            Action::ZoomIn(_) => Some(Action::ZoomIn(step)),
+            Action::ZoomOut(_) => Some(Action::ZoomOut(step)),
+            Action::PanLeft(_) => Some(Action::PanLeft(step)),
+            Action::PanRight(_) => Some(Action::PanRight(step)),
+            Action::PanUp(_) => Some(Action::PanUp(step)),
+            Action::PanDown(_) => Some(Action::PanDown(step)),
+            _ => None,
+        }
+    }
+
+    /// Help text; all step sizes of an action share one.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "exit",
+            Action::Cancel => "cancel loading",
+            Action::Open => "open image",
+            Action::ZoomIn(_) => "zoom in",
+            Action::ZoomOut(_) => "zoom out",
+            Action::PanLeft(_) => "move left",
+            Action::PanRight(_) => "move right",
+            Action::PanUp(_) => "move up",
+            Action::PanDown(_) => "move down",
+            Action::Reset => "reset view",
+            Action::ToggleHelp => "toggle help",
+            Action::NextRenderer => "change renderer",
+            Action::NextColors => "change colors",
+            Action::NextFilter => "change filter",
+            Action::NextImage => "next image",
+            Action::PreviousImage => "previous image",
+            Action::ToggleInspect => "inspect pixels",
+            Action::TogglePause => "pause animation or slideshow",
+            Action::NextFrame => "next frame",
+            Action::PreviousFrame => "previous frame",
+            Action::PlayFaster => "play faster",
+            Action::PlaySlower => "play slower",
+            Action::ToggleWatch => "reload on change",
+            Action::ToggleSlideshow => "slideshow",
+        }
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::Cancel => &["esc"],
+            Action::Open => &["o"],
+            Action::ZoomIn(Step::Small) => &["w", "+", "="],
+            Action::ZoomIn(Step::Large) => &["W"],
+            Action::ZoomOut(Step::Small) => &["s", "-"],
+            Action::ZoomOut(Step::Large) => &["S"],
+            Action::PanLeft(Step::Small) => &["h", "left"],
+            Action::PanLeft(Step::Large) => &["H", "shift+left"],
+            Action::PanLeft(Step::Edge) => &["ctrl+left"],
+            Action::PanRight(Step::Small) => &["l", "right"],
+            Action::PanRight(Step::Large) => &["L", "shift+right"],
+            Action::PanRight(Step::Edge) => &["ctrl+right"],
+            Action::PanUp(Step::Small) => &["k", "up"],
+            Action::PanUp(Step::Large) => &["K", "shift+up", "pageup"],
+            Action::PanUp(Step::Edge) => &["ctrl+up", "home"],
+            Action::PanDown(Step::Small) => &["j", "down"],
+            Action::PanDown(Step::Large) => &["J", "shift+down", "pagedown"],
+            Action::PanDown(Step::Edge) => &["ctrl+down", "end"],
+            Action::ZoomIn(Step::Edge) | Action::ZoomOut(Step::Edge) => &[],
+            Action::Reset => &["r"],
+            Action::ToggleHelp => &["y"],
+            Action::NextRenderer => &["m"],
+            Action::NextColors => &["c"],
+            Action::NextFilter => &["f"],
+            Action::NextImage => &["n"],
+            Action::PreviousImage => &["p"],
+            Action::ToggleInspect => &["i"],
+            Action::TogglePause => &["space"],
+            Action::NextFrame => &["."],
+            Action::PreviousFrame => &[","],
+            Action::PlayFaster => &["]"],
+            Action::PlaySlower => &["["],
+            Action::ToggleWatch => &["a"],
+            Action::ToggleSlideshow => &["t"],
+        }
+    }
+}
+
+/// A key press, with Shift folded into the character for printable keys
+/// since terminals disagree on whether they report it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct KeyBinding {
+    modifiers: KeyModifiers,
+    code: KeyCode,
+}
+
+impl KeyBinding {
+    pub fn new(modifiers: KeyModifiers, code: KeyCode) -> KeyBinding {
+        match code {
+            KeyCode::Char(c) => KeyBinding {
+                modifiers: modifiers - KeyModifiers::SHIFT,
+                code: if modifiers.contains(KeyModifiers::SHIFT) {
+                    KeyCode::Char(c.to_ascii_uppercase())
+                } else {
+                    code
+                },
+            },
+            _ => KeyBinding { modifiers, code },
+        }
+    }
+
+    /// Parses keys like `q`, `W`, `+`, `ctrl+o`, `shift+left`, `pagedown` or `f1`.
+    pub fn parse(text: &str) -> Option<KeyBinding> {
+        let (modifier_names, key) = match text {
+            "+" => ("", "+"),
+            _ if text.ends_with("++") => (&text[..text.len() - 2], "+"),
+            _ => text.rsplit_once('+').unwrap_or(("", text)),
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for name in modifier_names.split('+').filter(|name| !name.is_empty()) {
+            modifiers |= match name.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_ascii_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "esc" | "escape" => KeyCode::Esc,
+                "enter" | "return" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                name => KeyCode::F(name.strip_prefix('f')?.parse().ok()?),
+            },
+        };
+        Some(KeyBinding::new(modifiers, code))
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    /// Formats the key the way `parse` reads it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl+"),
+            (KeyModifiers::ALT, "alt+"),
+            (KeyModifiers::SHIFT, "shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::PageUp => f.write_str("pageup"),
+            KeyCode::PageDown => f.write_str("pagedown"),
+            code => f.write_str(&format!("{:?}", code).to_ascii_lowercase()),
+        }
+    }
+}
+
+/// Keys bound to each action.
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<KeyBinding>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        let bindings = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let keys = action.default_keys().iter();
+                (
+                    action,
+                    keys.filter_map(|key| KeyBinding::parse(key)).collect(),
+                )
+            })
+            .collect();
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    /// Replaces the keys of `action`.
+    pub fn bind(&mut self, action: Action, keys: Vec<KeyBinding>) {
+        for (bound, bound_keys) in &mut self.bindings {
+            if *bound == action {
+                *bound_keys = keys;
+                return;
+            }
+        }
+    }
+
+    /// Returns a pair of actions sharing a key, if any.
+    pub fn conflict(&self) -> Option<(Action, Action)> {
+        for (i, (action, keys)) in self.bindings.iter().enumerate() {
+            for (other, other_keys) in &self.bindings[i + 1..] {
+                if keys.iter().any(|key| other_keys.contains(key)) {
+                    return Some((*action, *other));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyBinding] {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map(|(_, keys)| keys.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Action bound to the key press, if any. Does not depend on the terminal,
+    /// so bindings can be checked with synthetic events.
+    pub fn resolve(&self, key_event: KeyEvent) -> Option<Action> {
+        let key = KeyBinding::new(key_event.modifiers, key_event.code);
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(modifiers: KeyModifiers, code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn shift_is_folded_into_characters() {
+        let upper = KeyBinding::parse("W").unwrap();
+        assert_eq!(KeyBinding::parse("shift+w"), Some(upper));
+        assert_eq!(
+            KeyBinding::new(KeyModifiers::SHIFT, KeyCode::Char('w')),
+            upper
+        );
+        assert_eq!(
+            KeyBinding::new(KeyModifiers::SHIFT, KeyCode::Char('W')),
+            upper
+        );
+        assert_eq!(
+            KeyBinding::new(KeyModifiers::SHIFT, KeyCode::Char('+')),
+            KeyBinding::parse("+").unwrap()
+        );
+        // keys without a character keep Shift
+        assert_ne!(KeyBinding::parse("shift+left"), KeyBinding::parse("left"));
+    }
+
+    #[test]
+    fn parses_modifiers_and_plus() {
+        assert_eq!(
+            KeyBinding::parse("ctrl+left"),
+            Some(KeyBinding::new(KeyModifiers::CONTROL, KeyCode::Left))
+        );
+        assert_eq!(
+            KeyBinding::parse("Control+Left"),
+            KeyBinding::parse("ctrl+left")
+        );
+        assert_eq!(
+            KeyBinding::parse("+"),
+            Some(KeyBinding::new(KeyModifiers::NONE, KeyCode::Char('+')))
+        );
+        assert_eq!(
+            KeyBinding::parse("ctrl++"),
+            Some(KeyBinding::new(KeyModifiers::CONTROL, KeyCode::Char('+')))
+        );
+        assert_eq!(KeyBinding::parse("ctrl+"), None);
+        assert_eq!(KeyBinding::parse("hyper+a"), None);
+        assert_eq!(KeyBinding::parse("nokey"), None);
+        assert_eq!(
+            KeyBinding::parse("f5"),
+            Some(KeyBinding::new(KeyModifiers::NONE, KeyCode::F(5)))
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for text in [
+            "q",
+            "W",
+            "+",
+            "ctrl++",
+            "ctrl+left",
+            "shift+up",
+            "space",
+            "pagedown",
+            "f1",
+        ] {
+            let key = KeyBinding::parse(text).unwrap();
+            assert_eq!(key.to_string(), text);
+            assert_eq!(KeyBinding::parse(&key.to_string()), Some(key));
+        }
+    }
+
+    #[test]
+    fn detects_conflicts() {
+        let mut keymap = Keymap::default();
+        assert_eq!(keymap.conflict(), None);
+
+        keymap.bind(Action::Open, vec![KeyBinding::parse("q").unwrap()]);
+        assert_eq!(keymap.conflict(), Some((Action::Open, Action::Quit)));
+
+        keymap.bind(Action::Quit, vec![KeyBinding::parse("ctrl+q").unwrap()]);
+        assert_eq!(keymap.conflict(), None);
+
+        // shift+s and S are the same key
+        keymap.bind(Action::Reset, vec![KeyBinding::parse("shift+s").unwrap()]);
+        assert_eq!(
+            keymap.conflict(),
+            Some((Action::ZoomOut(Step::Large), Action::Reset))
+        );
+    }
+
+    #[test]
+    fn resolves_default_keys() {
+        let keymap = Keymap::default();
+        let none = KeyModifiers::NONE;
+        let shift = KeyModifiers::SHIFT;
+        let ctrl = KeyModifiers::CONTROL;
+
+        let cases = [
+            (key(none, KeyCode::Char('q')), Some(Action::Quit)),
+            (key(none, KeyCode::Esc), Some(Action::Cancel)),
+            (
+                key(none, KeyCode::Char('w')),
+                Some(Action::ZoomIn(Step::Small)),
+            ),
+            (
+                key(none, KeyCode::Char('+')),
+                Some(Action::ZoomIn(Step::Small)),
+            ),
+            (
+                key(shift, KeyCode::Char('+')),
+                Some(Action::ZoomIn(Step::Small)),
+            ),
+            (
+                key(shift, KeyCode::Char('W')),
+                Some(Action::ZoomIn(Step::Large)),
+            ),
+            (
+                key(none, KeyCode::Char('W')),
+                Some(Action::ZoomIn(Step::Large)),
+            ),
+            (
+                key(shift, KeyCode::Char('w')),
+                Some(Action::ZoomIn(Step::Large)),
+            ),
+            (key(none, KeyCode::Left), Some(Action::PanLeft(Step::Small))),
+            (
+                key(shift, KeyCode::Left),
+                Some(Action::PanLeft(Step::Large)),
+            ),
+            (key(ctrl, KeyCode::Left), Some(Action::PanLeft(Step::Edge))),
+            (key(none, KeyCode::Home), Some(Action::PanUp(Step::Edge))),
+            (key(none, KeyCode::Char(' ')), Some(Action::TogglePause)),
+            (key(none, KeyCode::Char('t')), Some(Action::ToggleSlideshow)),
+            (key(ctrl, KeyCode::Char('q')), None),
+            (key(none, KeyCode::Char('z')), None),
+        ];
+        for (event, action) in cases {
+            assert_eq!(keymap.resolve(event), action, "{:?}", event);
+        }
+    }
+
+    #[test]
+    fn every_action_has_a_name() {
+        for action in Action::ALL {
+            assert_eq!(Action::from_name(action.name()), Some(action));
+        }
+        assert_eq!(
+            Action::from_name("zoom_out_edge"),
+            Some(Action::ZoomOut(Step::Edge))
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use std::io::stdout;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use crossterm::cursor::MoveTo;
+use crossterm::queue;
+use crossterm::style::Print;
+use image::RgbaImage;
+
+use crate::mod_display::encode_png;
+
+// maximum payload size of a single APC chunk
+const CHUNK_SIZE: usize = 4096;
+
+// images below this z-index are drawn under cells with a non-default background,
+// which keeps the help box and status line readable
+const Z_INDEX: i32 = i32::MIN / 2 - 1;
+
+const IMAGE_ID: u32 = 1;
+
+static PLACED: AtomicBool = AtomicBool::new(false);
+
+/// Transmits the frame as PNG and places it over `cols` x `rows` cells
+/// starting at the top-left corner.
+pub fn draw(frame: &RgbaImage, cols: u16, rows: u16) {
+    let control = format!(
+        "a=T,f=100,i={},c={},r={},z={},C=1,q=2",
+        IMAGE_ID, cols, rows, Z_INDEX
+    );
+    queue!(stdout(), MoveTo(0, 0), Print(transmit(frame, &control))).unwrap();
+    PLACED.store(true, Ordering::Relaxed);
+}
+
+/// Escape sequences placing the frame over `cols` x `rows` cells at the cursor,
+/// which is moved past the image. The image has no id, so it is never replaced.
+pub fn inline(frame: &RgbaImage, cols: u16, rows: u16) -> String {
+    transmit(frame, &format!("a=T,f=100,c={},r={},q=2", cols, rows))
+}
+
+// splits the PNG data into chunks, with the `control` keys sent on the first one
+fn transmit(frame: &RgbaImage, control: &str) -> String {
+    let data = STANDARD.encode(encode_png(frame));
+    let chunks = data.as_bytes().chunks(CHUNK_SIZE).collect::<Vec<_>>();
+
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        let control = if i == 0 {
+            format!("{},m={}", control, more)
+        } else {
+            format!("m={}", more)
+        };
+        // base64 output is always valid ASCII
+        let payload = std::str::from_utf8(chunk).unwrap();
+        out.push_str(&format!("\x1b_G{};{}\x1b\\", control, payload));
+    }
+    out
+}
+
+/// Deletes the image placed by `draw`, if any.
+pub fn clear() {
+    if PLACED.swap(false, Ordering::Relaxed) {
+        queue!(
+            stdout(),
+            Print(format!("\x1b_Ga=d,d=I,i={},q=2\x1b\\", IMAGE_ID))
+        )
+        .unwrap();
+    }
+}
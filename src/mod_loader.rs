@@ -0,0 +1,281 @@
+use std::fs::File;
+use std::io::{stdin, BufReader, Cursor, Error, ErrorKind, IsTerminal, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{
+    load_from_memory, open, AnimationDecoder, DynamicImage, ImageError, ImageFormat, ImageResult,
+};
+
+use crate::mod_animation::{Animation, Frame};
+use crate::mod_event_loop::Message;
+use crate::mod_pyramid::Pyramid;
+
+// spinner frames shown while decoding, ASCII so that every renderer can show them
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+// standard input can only be read once, so its data is kept for revisiting `-`
+static STDIN: OnceLock<Result<Vec<u8>, (ErrorKind, String)>> = OnceLock::new();
+
+/// Opens the image at `path`, or decodes standard input when `path` is `-`.
+pub fn open_image(path: &str) -> ImageResult<DynamicImage> {
+    if path != "-" {
+        return open(path);
+    }
+    load_from_memory(stdin_data()?)
+}
+
+/// Opens the image at `path` with all its frames.
+/// Formats without animation give a single frame.
+/// Gives up with an `Interrupted` error once `cancelled` is set.
+pub fn open_animation(path: &str, cancelled: &AtomicBool) -> ImageResult<Animation> {
+    if path == "-" {
+        let data = stdin_data()?;
+        return match image::guess_format(data) {
+            Ok(format @ (ImageFormat::Gif | ImageFormat::Png | ImageFormat::WebP)) => {
+                decode_animation(Cursor::new(data), format, cancelled)
+            }
+            _ => Ok(Animation::still(pyramid(
+                load_from_memory(data)?,
+                cancelled,
+            )?)),
+        };
+    }
+    match ImageFormat::from_path(path) {
+        Ok(format @ (ImageFormat::Gif | ImageFormat::Png | ImageFormat::WebP)) => {
+            let file = File::open(path).map_err(ImageError::IoError)?;
+            decode_animation(BufReader::new(file), format, cancelled)
+        }
+        _ => Ok(Animation::still(pyramid(open(path)?, cancelled)?)),
+    }
+}
+
+fn decode_animation<R: Read + 'static>(
+    reader: R,
+    format: ImageFormat,
+    cancelled: &AtomicBool,
+) -> ImageResult<Animation> {
+    let frames = match format {
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(reader)?;
+            if !decoder.is_apng() {
+                let image = DynamicImage::from_decoder(decoder)?;
+                return Ok(Animation::still(pyramid(image, cancelled)?));
+            }
+            decoder.apng().into_frames()
+        }
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(reader)?;
+            if !decoder.has_animation() {
+                let image = DynamicImage::from_decoder(decoder)?;
+                return Ok(Animation::still(pyramid(image, cancelled)?));
+            }
+            decoder.into_frames()
+        }
+        _ => GifDecoder::new(reader)?.into_frames(),
+    };
+
+    // the decoders hand out whole frames, already composed according
+    // to the disposal and blending of the previous ones
+    let frames = frames
+        .map(|frame| {
+            let frame = frame?;
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            Ok(Frame {
+                delay: Duration::from_secs_f64(
+                    numerator as f64 / denominator.max(1) as f64 / 1000.0,
+                ),
+                image: pyramid(DynamicImage::ImageRgba8(frame.into_buffer()), cancelled)?,
+            })
+        })
+        .collect::<ImageResult<Vec<_>>>()?;
+    if frames.is_empty() {
+        return Err(ImageError::IoError(Error::new(
+            ErrorKind::InvalidData,
+            "animation without frames",
+        )));
+    }
+    Ok(Animation::new(frames))
+}
+
+// building the pyramid takes about as long as decoding, so it is skipped
+// for images nobody waits for anymore
+fn pyramid(image: DynamicImage, cancelled: &AtomicBool) -> ImageResult<Pyramid> {
+    if cancelled.load(Ordering::Relaxed) {
+        return Err(interrupted("loading was canceled"));
+    }
+    Ok(Pyramid::new(image))
+}
+
+fn interrupted(message: &str) -> ImageError {
+    ImageError::IoError(Error::new(ErrorKind::Interrupted, message))
+}
+
+// standard input, read on first use
+fn stdin_data() -> ImageResult<&'static [u8]> {
+    let data = STDIN.get_or_init(|| {
+        // reading from the keyboard would compete with the viewer for key presses
+        if stdin().is_terminal() {
+            return Err((
+                ErrorKind::InvalidInput,
+                "standard input is a terminal, pipe an image into it".to_string(),
+            ));
+        }
+        let mut data = Vec::new();
+        stdin()
+            .read_to_end(&mut data)
+            .map_err(|e| (e.kind(), e.to_string()))?;
+        Ok(data)
+    });
+    match data {
+        Ok(data) => Ok(data),
+        Err((kind, message)) => Err(ImageError::IoError(Error::new(*kind, message.clone()))),
+    }
+}
+
+// an image waiting for the decoder
+struct Job {
+    path: String,
+    cancelled: Arc<AtomicBool>,
+    sender: Sender<ImageResult<Animation>>,
+}
+
+/// Decodes images on a worker thread, one at a time so that flipping through
+/// large images does not pile up decoders. A request made while another image
+/// is decoding waits for it, replacing the request that waited before.
+pub struct Decoder {
+    queue: Arc<(Mutex<Option<Job>>, Condvar)>,
+    notify: Sender<Message>,
+}
+
+impl Decoder {
+    /// Starts the worker, which sends `Message::Loaded` to `notify`
+    /// whenever an image is done.
+    pub fn new(notify: Sender<Message>) -> Decoder {
+        let queue = Arc::new((Mutex::new(None::<Job>), Condvar::new()));
+        let (jobs, worker_notify) = (Arc::clone(&queue), notify.clone());
+        thread::spawn(move || loop {
+            let (next, ready) = &*jobs;
+            let mut next = next.lock().unwrap();
+            let job = loop {
+                match next.take() {
+                    Some(job) => break job,
+                    None => next = ready.wait(next).unwrap(),
+                }
+            };
+            drop(next);
+            if job.cancelled.load(Ordering::Relaxed) {
+                continue;
+            }
+            // the receiver is gone when the job was cancelled meanwhile
+            if job
+                .sender
+                .send(open_animation(&job.path, &job.cancelled))
+                .is_ok()
+            {
+                let _ = worker_notify.send(Message::Loaded);
+            }
+        });
+        Decoder { queue, notify }
+    }
+
+    /// Queues `path` for decoding.
+    pub fn load(&self, path: &str) -> Loader {
+        let (sender, receiver) = channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let job = Job {
+            path: path.to_string(),
+            cancelled: Arc::clone(&cancelled),
+            sender,
+        };
+        let (next, ready) = &*self.queue;
+        if let Some(replaced) = next.lock().unwrap().replace(job) {
+            // its loader finds the channel closed and reports that
+            replaced.cancelled.store(true, Ordering::Relaxed);
+            drop(replaced);
+            let _ = self.notify.send(Message::Loaded);
+        }
+        ready.notify_one();
+        Loader {
+            receiver,
+            cancelled,
+            started: Instant::now(),
+        }
+    }
+}
+
+/// An image queued or being decoded by the `Decoder`.
+/// Dropping the loader cancels the job: the worker skips it, or stops
+/// before the decoded image is prepared for drawing.
+pub struct Loader {
+    receiver: Receiver<ImageResult<Animation>>,
+    cancelled: Arc<AtomicBool>,
+    started: Instant,
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Loader {
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn spinner(&self) -> char {
+        SPINNER[(self.elapsed().as_millis() / 100) as usize % SPINNER.len()]
+    }
+}
+
+pub enum ImageState {
+    Loading(Loader),
+    Ready(Animation),
+    Failed(ImageError),
+}
+
+impl ImageState {
+    pub fn load(path: &str, decoder: &Decoder) -> ImageState {
+        ImageState::Loading(decoder.load(path))
+    }
+
+    pub fn is_loading(&self) -> bool {
+        matches!(self, ImageState::Loading(_))
+    }
+
+    /// Takes the result of a finished loader or moves an animation to its
+    /// next frame when it is due. Returns whether the state changed.
+    pub fn update(&mut self) -> bool {
+        let loader = match self {
+            ImageState::Loading(loader) => loader,
+            ImageState::Ready(animation) => return animation.advance(),
+            ImageState::Failed(_) => return false,
+        };
+        *self = match loader.receiver.try_recv() {
+            Ok(Ok(image)) => ImageState::Ready(image),
+            Ok(Err(e)) => ImageState::Failed(e),
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Disconnected) if loader.cancelled.load(Ordering::Relaxed) => {
+                ImageState::Failed(interrupted("loading was replaced by another image"))
+            }
+            Err(TryRecvError::Disconnected) => ImageState::Failed(ImageError::IoError(
+                Error::other("image decoder stopped unexpectedly"),
+            )),
+        };
+        true
+    }
+
+    /// Stops waiting for the running loader, if any.
+    pub fn cancel(&mut self) {
+        if self.is_loading() {
+            *self = ImageState::Failed(interrupted("loading was canceled by user"));
+        }
+    }
+}
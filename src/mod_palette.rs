@@ -0,0 +1,274 @@
+use std::str::FromStr;
+
+use crossterm::style::Color;
+use image::{Rgba, RgbaImage};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColorMode {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    Mono,
+}
+
+impl ColorMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMode::TrueColor => "truecolor",
+            ColorMode::Ansi256 => "256",
+            ColorMode::Ansi16 => "16",
+            ColorMode::Mono => "mono",
+        }
+    }
+
+    pub fn next(&self) -> ColorMode {
+        match self {
+            ColorMode::TrueColor => ColorMode::Ansi256,
+            ColorMode::Ansi256 => ColorMode::Ansi16,
+            ColorMode::Ansi16 => ColorMode::Mono,
+            ColorMode::Mono => ColorMode::TrueColor,
+        }
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<ColorMode, String> {
+        match name {
+            "truecolor" | "24bit" => Ok(ColorMode::TrueColor),
+            "256" => Ok(ColorMode::Ansi256),
+            "16" => Ok(ColorMode::Ansi16),
+            "mono" => Ok(ColorMode::Mono),
+            _ => Err(format!(
+                "unknown color mode '{}' (expected auto, truecolor, 256, 16 or mono)",
+                name
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Dither {
+    None,
+    FloydSteinberg,
+    Ordered,
+}
+
+impl FromStr for Dither {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Dither, String> {
+        match name {
+            "none" => Ok(Dither::None),
+            "fs" | "floyd-steinberg" => Ok(Dither::FloydSteinberg),
+            "ordered" => Ok(Dither::Ordered),
+            _ => Err(format!(
+                "unknown dither '{}' (expected fs, ordered or none)",
+                name
+            )),
+        }
+    }
+}
+
+// xterm defaults for the 16 ANSI colours
+const ANSI16: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+// levels of the 6x6x6 colour cube in the xterm 256 colour palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// crossterm names of the 16 ANSI colours, in palette order
+const NAMED: [Color; 16] = [
+    Color::Black,
+    Color::DarkRed,
+    Color::DarkGreen,
+    Color::DarkYellow,
+    Color::DarkBlue,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+    Color::Grey,
+    Color::DarkGrey,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+];
+
+const BAYER4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Maps a colour to the nearest palette entry of `mode`,
+/// returning the palette index and the colour it displays as.
+pub fn nearest(rgb: [u8; 3], mode: ColorMode) -> (u8, [u8; 3]) {
+    match mode {
+        ColorMode::TrueColor => (0, rgb),
+        ColorMode::Ansi256 => {
+            let level = |v: u8| {
+                (0..6)
+                    .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - v as i32).abs())
+                    .unwrap()
+            };
+            let (r, g, b) = (level(rgb[0]), level(rgb[1]), level(rgb[2]));
+            let cube = [CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]];
+
+            let average = (rgb[0] as u32 + rgb[1] as u32 + rgb[2] as u32) / 3;
+            let step = (average.saturating_sub(3) / 10).min(23) as u8;
+            let gray_value = 8 + 10 * step;
+            let gray = [gray_value, gray_value, gray_value];
+
+            if distance(rgb, gray) < distance(rgb, cube) {
+                (232 + step, gray)
+            } else {
+                (16 + 36 * r as u8 + 6 * g as u8 + b as u8, cube)
+            }
+        }
+        ColorMode::Ansi16 => {
+            let i = (0..16).min_by_key(|&i| distance(rgb, ANSI16[i])).unwrap();
+            (i as u8, ANSI16[i])
+        }
+        ColorMode::Mono => {
+            let luma = (rgb[0] as u32 * 299 + rgb[1] as u32 * 587 + rgb[2] as u32 * 114) / 1000;
+            if luma < 128 {
+                (0, [0, 0, 0])
+            } else {
+                (7, [255, 255, 255])
+            }
+        }
+    }
+}
+
+/// Snaps every opaque pixel of the frame to the palette of `mode`,
+/// spreading the quantization error according to `dither`.
+pub fn quantize(frame: &mut RgbaImage, mode: ColorMode, dither: Dither) {
+    if mode == ColorMode::TrueColor {
+        return;
+    }
+    let (width, height) = frame.dimensions();
+
+    match dither {
+        Dither::None => {
+            for pixel in frame.pixels_mut().filter(|p| p[3] != 0) {
+                let (_, rgb) = nearest([pixel[0], pixel[1], pixel[2]], mode);
+                *pixel = Rgba([rgb[0], rgb[1], rgb[2], 255]);
+            }
+        }
+        Dither::Ordered => {
+            // roughly the distance between neighbouring palette colours
+            let spread = match mode {
+                ColorMode::Ansi256 => 40.0,
+                ColorMode::Ansi16 => 128.0,
+                _ => 255.0,
+            };
+            for (x, y, pixel) in frame.enumerate_pixels_mut().filter(|(_, _, p)| p[3] != 0) {
+                let threshold = (BAYER4[(y % 4) as usize][(x % 4) as usize] as f32 + 0.5) / 16.0;
+                let offset = (threshold - 0.5) * spread;
+                let rgb = [0, 1, 2].map(|c| (pixel[c] as f32 + offset).clamp(0.0, 255.0) as u8);
+                let (_, rgb) = nearest(rgb, mode);
+                *pixel = Rgba([rgb[0], rgb[1], rgb[2], 255]);
+            }
+        }
+        Dither::FloydSteinberg => {
+            let mut errors = vec![[0.0f32; 3]; (width * (height + 1) + 1) as usize];
+            for y in 0..height {
+                for x in 0..width {
+                    let pixel = frame.get_pixel_mut(x, y);
+                    if pixel[3] == 0 {
+                        continue;
+                    }
+                    let i = (y * width + x) as usize;
+                    let wanted = [0, 1, 2].map(|c| pixel[c] as f32 + errors[i][c]);
+                    let (_, rgb) = nearest(wanted.map(|v| v.clamp(0.0, 255.0) as u8), mode);
+                    *pixel = Rgba([rgb[0], rgb[1], rgb[2], 255]);
+
+                    let mut spread = |dx: i32, dy: u32, weight: f32| {
+                        let nx = x as i32 + dx;
+                        if nx >= 0 && nx < width as i32 {
+                            let j = ((y + dy) * width) as usize + nx as usize;
+                            for c in 0..3 {
+                                errors[j][c] += (wanted[c] - rgb[c] as f32) * weight;
+                            }
+                        }
+                    };
+                    spread(1, 0, 7.0 / 16.0);
+                    spread(-1, 1, 3.0 / 16.0);
+                    spread(0, 1, 5.0 / 16.0);
+                    spread(1, 1, 1.0 / 16.0);
+                }
+            }
+        }
+    }
+}
+
+/// Terminal colour showing `pixel` in the given colour mode.
+/// 16 colour and monochrome modes map to the named ANSI colours.
+pub fn to_color(pixel: Rgba<u8>, mode: ColorMode) -> Color {
+    let rgb = [pixel[0], pixel[1], pixel[2]];
+    match mode {
+        ColorMode::TrueColor => Color::Rgb {
+            r: rgb[0],
+            g: rgb[1],
+            b: rgb[2],
+        },
+        ColorMode::Ansi256 => Color::AnsiValue(nearest(rgb, mode).0),
+        ColorMode::Ansi16 | ColorMode::Mono => NAMED[nearest(rgb, mode).0 as usize],
+    }
+}
+
+/// Escape sequence selecting `color` as the foreground or background colour.
+/// Named colours use the plain SGR 30-37/90-97 codes, which are understood
+/// by terminals lacking the 256 colour extension.
+pub fn sgr(color: Color, foreground: bool) -> String {
+    let base = if foreground { 30 } else { 40 };
+    match color {
+        Color::Reset => format!("\x1b[{}m", base + 9),
+        Color::Rgb { r, g, b } => format!("\x1b[{};2;{};{};{}m", base + 8, r, g, b),
+        Color::AnsiValue(i) => format!("\x1b[{};5;{}m", base + 8, i),
+        named => match NAMED.iter().position(|&c| c == named).unwrap_or(7) {
+            i if i < 8 => format!("\x1b[{}m", base + i),
+            i => format!("\x1b[{}m", base + 60 + i - 8),
+        },
+    }
+}
+
+/// Hue in degrees, saturation and value in 0..=1.
+pub fn hsv(rgb: [u8; 3]) -> (f64, f64, f64) {
+    let [r, g, b] = rgb.map(|c| c as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    (0..3)
+        .map(|c| (a[c] as i32 - b[c] as i32).pow(2) as u32)
+        .sum()
+}
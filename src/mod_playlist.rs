@@ -0,0 +1,138 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use image::ImageFormat;
+
+/// Zoom and pan of an image, kept while other images are shown.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct View {
+    pub magnify: f64,
+    pub center: (f64, f64),
+}
+
+/// Interval of the slideshow when it is started without `--slideshow`.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Automatic advancing through the playlist.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Slideshow {
+    pub interval: Duration,
+    /// Start over after the last image instead of stopping.
+    pub looping: bool,
+    pub active: bool,
+    pub paused: bool,
+}
+
+/// Ordered list of the images to show, built from the command line.
+/// Directories are replaced by the supported images they contain
+/// and glob patterns the shell left alone by the files they match.
+pub struct Playlist {
+    paths: Vec<String>,
+    views: Vec<Option<View>>,
+    index: usize,
+}
+
+impl Playlist {
+    pub fn new(args: &[String]) -> Result<Playlist, Error> {
+        let mut paths = Vec::new();
+        for arg in args {
+            if !Path::new(arg).exists() && arg.contains(['*', '?', '[']) {
+                let pattern = glob::glob(arg)
+                    .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("{}: {}", arg, e)))?;
+                paths.extend(
+                    pattern
+                        .filter_map(|path| path.ok())
+                        .filter(|path| path.is_file() && is_supported(path))
+                        .map(|path| path.to_string_lossy().into_owned()),
+                );
+            } else if Path::new(arg).is_dir() {
+                let mut images = fs::read_dir(arg)?
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_file() && is_supported(path))
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>();
+                images.sort();
+                paths.extend(images);
+            } else {
+                // explicitly named files are kept so that errors show up in the viewer
+                paths.push(arg.clone());
+            }
+        }
+
+        if paths.is_empty() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("no supported images found in {}", args.join(", ")),
+            ));
+        }
+        Ok(Playlist {
+            views: vec![None; paths.len()],
+            paths,
+            index: 0,
+        })
+    }
+
+    pub fn current(&self) -> &str {
+        &self.paths[self.index]
+    }
+
+    /// 1-based position of the current image and the number of images.
+    pub fn position(&self) -> (usize, usize) {
+        (self.index + 1, self.paths.len())
+    }
+
+    /// View the current image was left with, if it was shown before.
+    pub fn view(&self) -> Option<View> {
+        self.views[self.index]
+    }
+
+    pub fn save_view(&mut self, view: View) {
+        self.views[self.index] = Some(view);
+    }
+
+    /// All images, in display order.
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.index + 1 == self.paths.len()
+    }
+
+    /// Puts the images in random order and starts over at the first one.
+    pub fn shuffle(&mut self) {
+        // xorshift seeded from the clock; the order only has to look random
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or(0);
+        let mut state = (nanos ^ ((process::id() as u64) << 32)) | 1;
+        for i in (1..self.paths.len()).rev() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let j = (state % (i as u64 + 1)) as usize;
+            self.paths.swap(i, j);
+            self.views.swap(i, j);
+        }
+        self.index = 0;
+    }
+
+    pub fn next(&mut self) {
+        self.index = (self.index + 1) % self.paths.len();
+    }
+
+    pub fn previous(&mut self) {
+        self.index = (self.index + self.paths.len() - 1) % self.paths.len();
+    }
+}
+
+fn is_supported(path: &Path) -> bool {
+    ImageFormat::from_path(path)
+        .map(|format| format.reading_enabled())
+        .unwrap_or(false)
+}
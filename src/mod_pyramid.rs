@@ -0,0 +1,188 @@
+use std::str::FromStr;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+// levels are halved until both sides are below this size
+const MIN_LEVEL_SIZE: u32 = 256;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Filter {
+    /// Triangle when shrinking, Nearest when magnifying so pixels stay sharp.
+    Auto,
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    Lanczos3,
+    /// Averages every source pixel covered by an output pixel.
+    Area,
+}
+
+impl Filter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Auto => "auto",
+            Filter::Nearest => "nearest",
+            Filter::Triangle => "triangle",
+            Filter::CatmullRom => "catmull-rom",
+            Filter::Gaussian => "gaussian",
+            Filter::Lanczos3 => "lanczos3",
+            Filter::Area => "area",
+        }
+    }
+
+    pub fn next(&self) -> Filter {
+        match self {
+            Filter::Auto => Filter::Nearest,
+            Filter::Nearest => Filter::Triangle,
+            Filter::Triangle => Filter::CatmullRom,
+            Filter::CatmullRom => Filter::Gaussian,
+            Filter::Gaussian => Filter::Lanczos3,
+            Filter::Lanczos3 => Filter::Area,
+            Filter::Area => Filter::Auto,
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Filter, String> {
+        match name {
+            "auto" => Ok(Filter::Auto),
+            "nearest" => Ok(Filter::Nearest),
+            "triangle" => Ok(Filter::Triangle),
+            "catmull-rom" => Ok(Filter::CatmullRom),
+            "gaussian" => Ok(Filter::Gaussian),
+            "lanczos3" => Ok(Filter::Lanczos3),
+            "area" => Ok(Filter::Area),
+            _ => Err(format!(
+                "unknown filter '{}' (expected auto, nearest, triangle, catmull-rom, gaussian, lanczos3 or area)",
+                name
+            )),
+        }
+    }
+}
+
+/// Image together with successively halved copies of it, so that a view
+/// only resamples the visible region of the smallest level that still has
+/// at least as many pixels as the output.
+pub struct Pyramid {
+    levels: Vec<DynamicImage>,
+}
+
+impl Pyramid {
+    pub fn new(image: DynamicImage) -> Pyramid {
+        let mut levels = vec![image];
+        loop {
+            let last = levels.last().unwrap();
+            let (width, height) = last.dimensions();
+            if width < MIN_LEVEL_SIZE * 2 && height < MIN_LEVEL_SIZE * 2 {
+                break;
+            }
+            let half = last.resize_exact(
+                (width / 2).max(1),
+                (height / 2).max(1),
+                FilterType::Triangle,
+            );
+            levels.push(half);
+        }
+        Pyramid { levels }
+    }
+
+    /// Size of the original image.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.levels[0].dimensions()
+    }
+
+    /// Pixel of the original image.
+    pub fn pixel(&self, x: u32, y: u32) -> Rgba<u8> {
+        self.levels[0].get_pixel(x, y)
+    }
+
+    /// The region `(l, t, w, h)` limited to the image, at least one pixel large.
+    pub fn clamp_region(&self, (l, t, w, h): (u32, u32, u32, u32)) -> (u32, u32, u32, u32) {
+        let (img_width, img_height) = self.dimensions();
+        let (l, t) = (l.min(img_width - 1), t.min(img_height - 1));
+        (l, t, w.clamp(1, img_width - l), h.clamp(1, img_height - t))
+    }
+
+    /// Resizes the region `(l, t, w, h)` of the original image to fit in
+    /// `win_width` x `win_height` while keeping its aspect ratio.
+    /// The region is clamped to the image bounds.
+    pub fn resample(
+        &self,
+        region: (u32, u32, u32, u32),
+        win_width: u32,
+        win_height: u32,
+        filter: Filter,
+    ) -> RgbaImage {
+        let (l, t, w, h) = self.clamp_region(region);
+
+        // output size
+        let ratio = f64::min(win_width as f64 / w as f64, win_height as f64 / h as f64);
+        let out_width = ((w as f64 * ratio).round() as u32).clamp(1, win_width);
+        let out_height = ((h as f64 * ratio).round() as u32).clamp(1, win_height);
+
+        // smallest level keeping at least one source pixel per output pixel
+        let mut level = 0;
+        while level + 1 < self.levels.len()
+            && w >> (level + 1) >= out_width
+            && h >> (level + 1) >= out_height
+        {
+            level += 1;
+        }
+
+        let img = &self.levels[level];
+        let (level_width, level_height) = img.dimensions();
+        let (l, t) = (
+            (l >> level).min(level_width - 1),
+            (t >> level).min(level_height - 1),
+        );
+        let (w, h) = (
+            (w >> level).clamp(1, level_width - l),
+            (h >> level).clamp(1, level_height - t),
+        );
+        // the region of the chosen level is at most about twice the output size
+        // per side, so copying it out is cheap
+        let region = img.crop_imm(l, t, w, h);
+        let filter_type = match filter {
+            Filter::Auto if out_width > w || out_height > h => FilterType::Nearest,
+            Filter::Auto | Filter::Triangle => FilterType::Triangle,
+            Filter::Nearest => FilterType::Nearest,
+            Filter::CatmullRom => FilterType::CatmullRom,
+            Filter::Gaussian => FilterType::Gaussian,
+            Filter::Lanczos3 => FilterType::Lanczos3,
+            Filter::Area => return area_resize(&region.into_rgba8(), out_width, out_height),
+        };
+        region
+            .resize_exact(out_width, out_height, filter_type)
+            .into_rgba8()
+    }
+}
+
+/// Box filter: every output pixel is the average of the source pixels it covers.
+fn area_resize(img: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let (src_width, src_height) = img.dimensions();
+    let span = |i: u32, size: u32, src_size: u32| {
+        let start = (i as u64 * src_size as u64 / size as u64) as u32;
+        let end = ((i as u64 + 1) * src_size as u64 / size as u64) as u32;
+        start..end.max(start + 1).min(src_size)
+    };
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let mut sum = [0u32; 4];
+        let mut count = 0;
+        for sy in span(y, height, src_height) {
+            for sx in span(x, width, src_width) {
+                let pixel = img.get_pixel(sx, sy);
+                for c in 0..4 {
+                    sum[c] += pixel[c] as u32;
+                }
+                count += 1;
+            }
+        }
+        image::Rgba(sum.map(|v| (v / count) as u8))
+    })
+}
@@ -0,0 +1,161 @@
+use std::io::stdout;
+
+use crossterm::cursor::MoveTo;
+use crossterm::style::{Color, Print, ResetColor};
+use crossterm::{queue, terminal};
+
+use crate::mod_palette::sgr;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: Color,
+    pub bg: Color,
+}
+
+impl Cell {
+    /// What a cell looks like right after the screen has been cleared.
+    pub const BLANK: Cell = Cell {
+        ch: ' ',
+        fg: Color::Reset,
+        bg: Color::Reset,
+    };
+
+    // never drawn, so a cell compared against it is always redrawn
+    const UNKNOWN: Cell = Cell {
+        ch: '\0',
+        fg: Color::Reset,
+        bg: Color::Reset,
+    };
+}
+
+/// Grid of cells mirroring the terminal. Only the cells that changed since
+/// the previous `flush` are sent to the terminal.
+#[derive(Default)]
+pub struct Screen {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    previous: Vec<Cell>,
+    /// Whether the last frame showed an image of a graphics protocol,
+    /// which the cells know nothing about.
+    graphics: bool,
+}
+
+impl Screen {
+    /// Blank grid that is not tied to the terminal, for rendering to text with `line`.
+    pub fn new(width: u16, height: u16) -> Screen {
+        let cells = vec![Cell::BLANK; width as usize * height as usize];
+        Screen {
+            width,
+            height,
+            previous: cells.clone(),
+            cells,
+            graphics: false,
+        }
+    }
+
+    /// Starts a new frame of `width` x `height` cells, all blank.
+    /// The terminal is cleared and fully redrawn when this frame or the
+    /// previous one shows a `graphics` protocol image, or when its size changed.
+    pub fn begin(&mut self, width: u16, height: u16, graphics: bool) {
+        let len = width as usize * height as usize;
+        let previous_graphics = std::mem::replace(&mut self.graphics, graphics);
+        let redraw_all = graphics || previous_graphics;
+        if redraw_all || width != self.width || height != self.height {
+            queue!(
+                stdout(),
+                ResetColor,
+                terminal::Clear(terminal::ClearType::All)
+            )
+            .unwrap();
+            self.previous = vec![Cell::BLANK; len];
+        }
+        self.width = width;
+        self.height = height;
+        self.cells = vec![Cell::BLANK; len];
+    }
+
+    /// Forces row `y` to be redrawn, for rows written to outside the grid.
+    pub fn invalidate_row(&mut self, y: u16) {
+        if y < self.height {
+            let start = y as usize * self.width as usize;
+            self.previous[start..start + self.width as usize].fill(Cell::UNKNOWN);
+        }
+    }
+
+    pub fn set(&mut self, x: u16, y: u16, cell: Cell) {
+        if x < self.width && y < self.height {
+            self.cells[y as usize * self.width as usize + x as usize] = cell;
+        }
+    }
+
+    /// Writes `text` starting at (x, y), clipped at the right edge.
+    pub fn print(&mut self, x: u16, y: u16, text: &str, fg: Color, bg: Color) {
+        for (i, ch) in text.chars().enumerate() {
+            self.set(x + i as u16, y, Cell { ch, fg, bg });
+        }
+    }
+
+    /// Builds row `y` as text. Colours are emitted only when they change,
+    /// so a row of default-coloured cells stays plain text.
+    pub fn line(&self, y: u16) -> String {
+        let mut line = String::new();
+        let (mut fg, mut bg) = (Color::Reset, Color::Reset);
+        let start = y as usize * self.width as usize;
+        for cell in &self.cells[start..start + self.width as usize] {
+            if cell.fg != fg {
+                line.push_str(&sgr(cell.fg, true));
+                fg = cell.fg;
+            }
+            if cell.bg != bg {
+                line.push_str(&sgr(cell.bg, false));
+                bg = cell.bg;
+            }
+            line.push(cell.ch);
+        }
+        if fg != Color::Reset || bg != Color::Reset {
+            line.push_str("\x1b[0m");
+        }
+        line
+    }
+
+    /// Sends the changed cells to the terminal. Changed cells on the same row
+    /// are written as one run, and colours are only set when they differ
+    /// from the ones currently active.
+    pub fn flush(&mut self) {
+        let mut out = String::new();
+        let mut cursor = None;
+        let (mut fg, mut bg) = (None, None);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = y as usize * self.width as usize + x as usize;
+                let cell = self.cells[i];
+                if cell == self.previous[i] {
+                    continue;
+                }
+
+                if cursor != Some((x, y)) {
+                    out.push_str(&format!("\x1b[{};{}H", y + 1, x + 1));
+                }
+                if fg != Some(cell.fg) {
+                    out.push_str(&sgr(cell.fg, true));
+                    fg = Some(cell.fg);
+                }
+                if bg != Some(cell.bg) {
+                    out.push_str(&sgr(cell.bg, false));
+                    bg = Some(cell.bg);
+                }
+                out.push(cell.ch);
+                cursor = Some((x + 1, y));
+            }
+        }
+
+        if !out.is_empty() {
+            queue!(stdout(), Print(out), ResetColor).unwrap();
+        }
+        self.previous.clone_from(&self.cells);
+        queue!(stdout(), MoveTo(0, self.height.saturating_sub(1))).unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt::Write as _;
+use std::io::stdout;
+
+use crossterm::cursor::MoveTo;
+use crossterm::queue;
+use crossterm::style::Print;
+use image::RgbaImage;
+
+// sixel terminals commonly support up to 256 colour registers
+const MAX_COLORS: usize = 256;
+
+/// Encodes the frame as a Sixel image and draws it at the top-left corner.
+pub fn draw(frame: &RgbaImage) {
+    queue!(stdout(), MoveTo(0, 0), Print(encode(frame))).unwrap();
+}
+
+/// Sixel data of the frame, drawn at the cursor position.
+pub fn encode(frame: &RgbaImage) -> String {
+    let (width, height) = frame.dimensions();
+    let (palette, indices) = quantize(frame, MAX_COLORS);
+
+    // P2=1: pixels without a colour keep the terminal background
+    let mut out = String::new();
+    write!(out, "\x1bP0;1;0q\"1;1;{};{}", width, height).unwrap();
+    for (i, color) in palette.iter().enumerate() {
+        write!(
+            out,
+            "#{};2;{};{};{}",
+            i,
+            color[0] as u32 * 100 / 255,
+            color[1] as u32 * 100 / 255,
+            color[2] as u32 * 100 / 255
+        )
+        .unwrap();
+    }
+
+    // every band is 6 pixels tall; each colour used in the band is drawn
+    // as a separate pass over the same band
+    let mut sixels = vec![0u8; width as usize];
+    for band in (0..height).step_by(6) {
+        let band_height = (height - band).min(6);
+        let mut used = vec![false; palette.len()];
+        for y in band..band + band_height {
+            for x in 0..width {
+                if let Some(i) = indices[(y * width + x) as usize] {
+                    used[i as usize] = true;
+                }
+            }
+        }
+
+        let mut first = true;
+        for (color, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+            sixels.iter_mut().for_each(|s| *s = 0);
+            for dy in 0..band_height {
+                for x in 0..width {
+                    if indices[((band + dy) * width + x) as usize] == Some(color as u8) {
+                        sixels[x as usize] |= 1 << dy;
+                    }
+                }
+            }
+
+            if !first {
+                out.push('$');
+            }
+            first = false;
+            write!(out, "#{}", color).unwrap();
+            push_run_length(&mut out, &sixels);
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+fn push_run_length(out: &mut String, sixels: &[u8]) {
+    let mut x = 0;
+    while x < sixels.len() {
+        let mut run = 1;
+        while x + run < sixels.len() && sixels[x + run] == sixels[x] {
+            run += 1;
+        }
+        let c = (sixels[x] + 0x3f) as char;
+        if run > 3 {
+            write!(out, "!{}{}", run, c).unwrap();
+        } else {
+            (0..run).for_each(|_| out.push(c));
+        }
+        x += run;
+    }
+}
+
+/// Reduces the frame to at most `max_colors` colours with median cut over a
+/// 15-bit colour histogram. Returns the palette and a palette index for every
+/// pixel, or `None` for transparent pixels.
+fn quantize(frame: &RgbaImage, max_colors: usize) -> (Vec<[u8; 3]>, Vec<Option<u8>>) {
+    let key = |p: &image::Rgba<u8>| {
+        ((p[0] as usize >> 3) << 10) | ((p[1] as usize >> 3) << 5) | (p[2] as usize >> 3)
+    };
+    let channel = |k: usize, c: usize| ((k >> (10 - c * 5)) & 0x1f) as u8;
+
+    // build histogram
+    let mut histogram = vec![0u32; 1 << 15];
+    for pixel in frame.pixels().filter(|p| p[3] != 0) {
+        histogram[key(pixel)] += 1;
+    }
+    let entries = (0..histogram.len())
+        .filter(|&k| histogram[k] > 0)
+        .collect::<Vec<_>>();
+
+    // split the box with the widest channel range until the palette is full
+    let mut boxes = if entries.is_empty() {
+        vec![]
+    } else {
+        vec![entries]
+    };
+    while boxes.len() < max_colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (c, range) = (0..3)
+                    .map(|c| {
+                        let min = b.iter().map(|&k| channel(k, c)).min().unwrap();
+                        let max = b.iter().map(|&k| channel(k, c)).max().unwrap();
+                        (c, max - min)
+                    })
+                    .max_by_key(|&(_, range)| range)
+                    .unwrap();
+                (i, c, range)
+            })
+            .max_by_key(|&(_, _, range)| range);
+        let Some((i, c, _)) = widest else {
+            break;
+        };
+
+        let mut b = boxes.swap_remove(i);
+        b.sort_by_key(|&k| channel(k, c));
+        let total = b.iter().map(|&k| histogram[k] as u64).sum::<u64>();
+        let mut acc = 0;
+        let mut split = 1;
+        for (j, &k) in b.iter().enumerate().take(b.len() - 1) {
+            acc += histogram[k] as u64;
+            split = j + 1;
+            if acc * 2 >= total {
+                break;
+            }
+        }
+        let rest = b.split_off(split);
+        boxes.push(b);
+        boxes.push(rest);
+    }
+
+    // average each box into a palette entry
+    let mut lookup = vec![0u8; 1 << 15];
+    let palette = boxes
+        .iter()
+        .enumerate()
+        .map(|(i, b)| {
+            let mut sum = [0u64; 4];
+            for &k in b {
+                let n = histogram[k] as u64;
+                for (c, s) in sum.iter_mut().take(3).enumerate() {
+                    *s += (channel(k, c) as u64 * 255 / 31) * n;
+                }
+                sum[3] += n;
+                lookup[k] = i as u8;
+            }
+            [
+                (sum[0] / sum[3]) as u8,
+                (sum[1] / sum[3]) as u8,
+                (sum[2] / sum[3]) as u8,
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let indices = frame
+        .pixels()
+        .map(|p| {
+            if p[3] == 0 {
+                None
+            } else {
+                Some(lookup[key(p)])
+            }
+        })
+        .collect();
+    (palette, indices)
+}